@@ -0,0 +1,92 @@
+//! Compares translating a book paragraph by paragraph with `translate_simple` and `translate`
+//! to translating it with `translate_batch`, in this process and in a `LouisPool`.
+//!
+//! Run with `cargo bench --bench translate_batch`. The number of paragraphs can be passed
+//! in the `PARAGRAPHS` environment variable, and the table in `TABLE`.
+
+extern crate louis;
+
+use louis::modes::TranslationModes;
+use louis::{process, Louis, LouisPool};
+use std::env;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SENTENCES: &[&str] = &[
+    "It was the best of times, it was the worst of times,",
+    "it was the age of wisdom, it was the age of foolishness,",
+    "it was the epoch of belief, it was the epoch of incredulity,",
+    "it was the season of Light, it was the season of Darkness,",
+    "it was the spring of hope, it was the winter of despair.",
+];
+
+fn measure<F: FnOnce() -> usize>(name: &str, paragraphs: usize, f: F) -> Duration {
+    let start = Instant::now();
+    let translated = f();
+    let elapsed = start.elapsed();
+    assert_eq!(translated, paragraphs, "{}: some paragraphs failed", name);
+    println!(
+        "{:<28} {:>10.2?} {:>10.0} paragraphs/s",
+        name,
+        elapsed,
+        paragraphs as f64 / elapsed.as_secs_f64()
+    );
+    elapsed
+}
+
+fn main() {
+    // The pool re-executes this benchmark as its helpers
+    process::run_worker_if_requested();
+
+    let paragraphs: usize = env::var("PARAGRAPHS")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(20_000);
+    let table = env::var("TABLE").unwrap_or_else(|_| "en-us-g2.ctb".to_owned());
+    let book: Vec<String> = (0..paragraphs)
+        .map(|i| SENTENCES[..1 + i % SENTENCES.len()].join(" "))
+        .collect();
+    let mode = TranslationModes::empty();
+
+    let louis = Louis::new().unwrap();
+    // Compile the table before measuring
+    louis.load_table(&table).unwrap();
+
+    let simple = measure("translate_simple", paragraphs, || {
+        book.iter()
+            .filter(|p| louis.translate_simple(&table, p, false, mode).is_ok())
+            .count()
+    });
+    measure("translate", paragraphs, || {
+        book.iter()
+            .filter(|p| louis.translate(&table, p, mode).is_ok())
+            .count()
+    });
+    let batch = measure("translate_batch", paragraphs, || {
+        louis
+            .translate_batch(&table, &book, mode)
+            .iter()
+            .filter(|t| t.is_ok())
+            .count()
+    });
+
+    let workers = thread::available_parallelism().map_or(1, |n| n.get());
+    let pool = LouisPool::new(workers).unwrap();
+    let pooled = measure(
+        &format!("LouisPool ({} workers)", workers),
+        paragraphs,
+        || {
+            pool.translate_batch(&table, &book, mode)
+                .iter()
+                .filter(|t| t.is_ok())
+                .count()
+        },
+    );
+
+    println!();
+    println!(
+        "translate_batch is {:.2}x as fast as translate_simple, {:.2}x with the pool",
+        simple.as_secs_f64() / batch.as_secs_f64(),
+        simple.as_secs_f64() / pooled.as_secs_f64()
+    );
+}
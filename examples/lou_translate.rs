@@ -3,6 +3,7 @@ extern crate louis;
 extern crate pretty_env_logger;
 
 use clap::{App, Arg};
+use louis::modes::TranslationModes;
 use louis::Louis;
 use std::io::{self, BufRead, Write};
 
@@ -45,11 +46,17 @@ fn main() {
     let stdout = io::stdout();
     let mut olock = stdout.lock();
     for line in ilock.lines() {
-        writeln!(olock, "{}", louis.translate_simple(
-            &table, 
-            &line.unwrap(), 
-            matches.is_present("backward"), 
-            0)
-        );
+        match louis.translate_simple(
+            &table,
+            &line.unwrap(),
+            matches.is_present("backward"),
+            TranslationModes::empty(),
+        ) {
+            Ok(translation) => writeln!(olock, "{}", translation).unwrap(),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
     }
 }
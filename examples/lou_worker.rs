@@ -0,0 +1,16 @@
+//! A helper process for `LouisPool::with_program`, serving requests on stdin and stdout.
+
+extern crate louis;
+extern crate pretty_env_logger;
+
+use std::process;
+
+fn main() {
+    // Logs go to stderr, so they don't interfere with the responses
+    pretty_env_logger::init();
+
+    if let Err(e) = louis::process::run_worker() {
+        eprintln!("lou_worker: {}", e);
+        process::exit(1);
+    }
+}
@@ -10,13 +10,15 @@ use std::path::PathBuf;
 fn main() {
     let mut builder = bindgen::Builder::default().header("wrapper.h");
 
+    // The bindings declare `_lou_defaultTableResolver` and `_lou_resolveTable`, which are internal
+    // to liblouis and whose ownership rules only hold for the 3.7 releases we vendor
     match pkg_config::Config::new()
-        .atleast_version("3.1.0")
+        .range_version("3.7.0".."3.8.0")
         .probe("liblouis")
     {
         Ok(system_liblouis) => {
             info!(
-                "Found recent system liblouis via pkg-config. Version: {}",
+                "Found compatible system liblouis via pkg-config. Version: {}",
                 system_liblouis.version
             );
         }
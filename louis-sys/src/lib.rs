@@ -28,8 +28,6 @@ impl Drop for ThreadUnsafetyToken {
     fn drop(&mut self) {
         if HAVE_TOKEN.swap(true, Ordering::SeqCst) {
             panic!("Tried to drop ThreadUnsafetyToken back but we're also holding one. Something is very wrong and UB is likely!")
-        } else {
-            ()
         }
     }
 }
@@ -37,6 +35,30 @@ impl Drop for ThreadUnsafetyToken {
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
+// These are internal to liblouis, which is why build.rs only accepts a system liblouis 3.7,
+// the version that is vendored and whose behaviour is described here
+extern "C" {
+    /// The table resolver liblouis uses unless another one is registered using `lou_registerTableResolver`.
+    /// It isn't declared in `liblouis.h`, but is exported by the library.
+    ///
+    /// Registering it again restores the default behaviour. When called directly, the caller
+    /// has to `free` the returned array and each of its strings.
+    pub fn _lou_defaultTableResolver(
+        tableList: *const ::std::os::raw::c_char,
+        base: *const ::std::os::raw::c_char,
+    ) -> *mut *mut ::std::os::raw::c_char;
+
+    /// Resolves `tableList` to the table files it refers to, using the registered table resolver.
+    /// Like `_lou_defaultTableResolver`, this is exported but not declared in `liblouis.h`.
+    ///
+    /// Returns NULL if the tables can't be found. Otherwise, the caller has to `free`
+    /// the returned array and each of its strings.
+    pub fn _lou_resolveTable(
+        tableList: *const ::std::os::raw::c_char,
+        base: *const ::std::os::raw::c_char,
+    ) -> *mut *mut ::std::os::raw::c_char;
+}
+
 #[cfg(test)]
 mod tests;
 
@@ -58,7 +58,7 @@ fn liblouis_tables() {
         let list_begin = lou_listTables();
         loop {
             let ptr = *(list_begin.offset(offset));
-            if ptr == std::ptr::null() {
+            if ptr.is_null() {
                 break;
             }
             let table_name = Path::new(CStr::from_ptr(ptr).to_str().unwrap())
@@ -70,7 +70,7 @@ fn liblouis_tables() {
             offset += 1;
         }
     };
-    println!("");
+    println!();
     assert!(offset > 0, "No tables were found.");
     println!("Found {} tables in total.", offset);
 }}
@@ -0,0 +1,259 @@
+use louis_sys::widechar;
+
+use std::fmt;
+use std::iter::FromIterator;
+use std::ops::Deref;
+
+use super::{Error, Louis, Result};
+
+/// Marks a `widechar` as a braille cell rather than a character in liblouis' dots encoding
+const LOUIS_DOTS_MARKER: widechar = 0x8000;
+
+/// The first character of the Unicode braille patterns block, the empty cell
+const UNICODE_BRAILLE_BASE: u32 = 0x2800;
+
+bitflags! {
+    /// A single braille cell, stored as a bitmask of its raised dots.
+    ///
+    /// ```
+    /// # use louis::braille::BrailleCell;
+    /// let b = BrailleCell::DOT_1 | BrailleCell::DOT_2;
+    /// assert_eq!(format!("{:?}", b), "DOT_1 | DOT_2");
+    /// assert!(BrailleCell::empty().is_empty());
+    /// ```
+    #[derive(Default)]
+    pub struct BrailleCell: u8 {
+        const DOT_1 = 0x01;
+        const DOT_2 = 0x02;
+        const DOT_3 = 0x04;
+        const DOT_4 = 0x08;
+        const DOT_5 = 0x10;
+        const DOT_6 = 0x20;
+        const DOT_7 = 0x40;
+        const DOT_8 = 0x80;
+    }
+}
+
+impl BrailleCell {
+    /// Converts a cell in liblouis' own dots encoding, as output with `modes::DOTS_LOUIS`,
+    /// returning `None` if `dots` is an ordinary character.
+    pub fn from_louis_dots(dots: widechar) -> Option<Self> {
+        if dots & !0xff == LOUIS_DOTS_MARKER {
+            Some(BrailleCell::from_bits_truncate(dots as u8))
+        } else {
+            None
+        }
+    }
+
+    /// Returns this cell in liblouis' own dots encoding, `0x8000 | bits`.
+    pub fn to_louis_dots(self) -> widechar {
+        LOUIS_DOTS_MARKER | widechar::from(self.bits())
+    }
+
+    /// Converts a character from the Unicode braille patterns block,
+    /// returning `None` for any other character.
+    ///
+    /// ```
+    /// # use louis::braille::BrailleCell;
+    /// assert_eq!(BrailleCell::from_unicode('⠃'), Some(BrailleCell::DOT_1 | BrailleCell::DOT_2));
+    /// assert_eq!(BrailleCell::from_unicode('b'), None);
+    /// ```
+    pub fn from_unicode(c: char) -> Option<Self> {
+        let offset = (c as u32).wrapping_sub(UNICODE_BRAILLE_BASE);
+        if offset <= 0xff {
+            Some(BrailleCell::from_bits_truncate(offset as u8))
+        } else {
+            None
+        }
+    }
+
+    /// Returns this cell as a character from the Unicode braille patterns block.
+    pub fn to_unicode(self) -> char {
+        // Every u8 offset lies within the block, so this can't fail
+        std::char::from_u32(UNICODE_BRAILLE_BASE + u32::from(self.bits())).unwrap()
+    }
+
+    /// Parses a cell in dot number notation, such as `145`. The empty cell is written as `0`.
+    pub fn from_dot_numbers(notation: &str) -> Result<Self> {
+        if notation == "0" {
+            return Ok(BrailleCell::empty());
+        }
+        let invalid = || Error::InvalidInput(format!("\"{}\" is not a braille cell", notation));
+        if notation.is_empty() {
+            return Err(invalid());
+        }
+        let mut cell = BrailleCell::empty();
+        for digit in notation.chars() {
+            let dot = match digit.to_digit(10) {
+                Some(n @ 1..=8) => BrailleCell::from_bits_truncate(1 << (n - 1)),
+                _ => return Err(invalid()),
+            };
+            if cell.contains(dot) {
+                return Err(invalid());
+            }
+            cell |= dot;
+        }
+        Ok(cell)
+    }
+
+    /// Returns this cell in dot number notation, such as `145`. The empty cell is written as `0`.
+    pub fn to_dot_numbers(self) -> String {
+        if self.is_empty() {
+            return "0".to_owned();
+        }
+        (1..=8)
+            .filter(|n| self.bits() & (1 << (n - 1)) != 0)
+            .map(|n| std::char::from_digit(n, 10).unwrap())
+            .collect()
+    }
+}
+
+impl fmt::Display for BrailleCell {
+    /// Formats the cell as a Unicode braille pattern
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_unicode())
+    }
+}
+
+/// A sequence of braille cells, independent of how they are encoded.
+///
+/// Converts losslessly between Unicode braille, liblouis' own dots encoding and dot number notation.
+/// Conversion to and from braille ASCII depends on the display table used.
+///
+/// ```
+/// # use louis::braille::BrailleString;
+/// let braille = BrailleString::from_dot_numbers("145-1-123").unwrap();
+/// assert_eq!(braille.to_unicode(), "⠙⠁⠇");
+/// assert_eq!(BrailleString::from_unicode("⠙⠁⠇").unwrap(), braille);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct BrailleString(Vec<BrailleCell>);
+
+impl BrailleString {
+    pub fn new() -> Self {
+        BrailleString(Vec::new())
+    }
+
+    /// Parses a string of characters from the Unicode braille patterns block.
+    pub fn from_unicode(braille: &str) -> Result<Self> {
+        braille
+            .chars()
+            .map(|c| {
+                BrailleCell::from_unicode(c).ok_or_else(|| {
+                    Error::InvalidInput(format!("{:?} is not a Unicode braille pattern", c))
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the cells as characters from the Unicode braille patterns block.
+    pub fn to_unicode(&self) -> String {
+        self.0.iter().map(|cell| cell.to_unicode()).collect()
+    }
+
+    /// Converts braille in liblouis' own dots encoding, as output with `modes::DOTS_LOUIS`.
+    pub fn from_louis_dots(dots: &[widechar]) -> Result<Self> {
+        dots.iter()
+            .map(|&d| {
+                BrailleCell::from_louis_dots(d).ok_or_else(|| {
+                    Error::InvalidInput(format!(
+                        "{:#x} is not a braille cell in liblouis' encoding",
+                        d
+                    ))
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the cells in liblouis' own dots encoding.
+    pub fn to_louis_dots(&self) -> Vec<widechar> {
+        self.0.iter().map(|cell| cell.to_louis_dots()).collect()
+    }
+
+    /// Parses cells in dot number notation separated by `-`, such as `145-1-123`.
+    /// The empty cell is written as `0`, and the empty string contains no cells.
+    pub fn from_dot_numbers(notation: &str) -> Result<Self> {
+        if notation.is_empty() {
+            return Ok(BrailleString::new());
+        }
+        notation
+            .split('-')
+            .map(BrailleCell::from_dot_numbers)
+            .collect()
+    }
+
+    /// Returns the cells in dot number notation separated by `-`, such as `145-1-123`.
+    pub fn to_dot_numbers(&self) -> String {
+        self.0
+            .iter()
+            .map(|cell| cell.to_dot_numbers())
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+
+    /// Converts braille ASCII using the display table given by `table_names`, see `Louis::char_to_dots`.
+    ///
+    /// ```
+    /// # use louis::{Louis, braille::BrailleString};
+    /// let louis = Louis::new().unwrap();
+    /// let braille = BrailleString::from_ascii(&louis, "en-us-comp8.ctb", "dal").unwrap();
+    /// assert_eq!(braille.to_dot_numbers(), "145-1-123");
+    /// ```
+    pub fn from_ascii(louis: &Louis, table_names: &str, ascii: &str) -> Result<Self> {
+        louis.char_to_dots(table_names, ascii).map(BrailleString)
+    }
+
+    /// Returns the cells as braille ASCII using the display table given by `table_names`, see `Louis::dots_to_char`.
+    pub fn to_ascii(&self, louis: &Louis, table_names: &str) -> Result<String> {
+        louis.dots_to_char(table_names, &self.0)
+    }
+
+    pub fn cells(&self) -> &[BrailleCell] {
+        &self.0
+    }
+
+    pub fn into_cells(self) -> Vec<BrailleCell> {
+        self.0
+    }
+}
+
+impl Deref for BrailleString {
+    type Target = [BrailleCell];
+
+    fn deref(&self) -> &[BrailleCell] {
+        &self.0
+    }
+}
+
+impl From<Vec<BrailleCell>> for BrailleString {
+    fn from(cells: Vec<BrailleCell>) -> Self {
+        BrailleString(cells)
+    }
+}
+
+impl FromIterator<BrailleCell> for BrailleString {
+    fn from_iter<I: IntoIterator<Item = BrailleCell>>(iter: I) -> Self {
+        BrailleString(iter.into_iter().collect())
+    }
+}
+
+impl fmt::Display for BrailleString {
+    /// Formats the cells as Unicode braille patterns
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_unicode())
+    }
+}
+
+/// Interprets the output of a translation as braille cells if it consists entirely of
+/// cells in either liblouis' own or the Unicode encoding, as it does with `modes::DOTS_IO`.
+// `widechar` is only `u32` in UCS-4 builds of liblouis, and `u16` otherwise
+#[allow(clippy::useless_conversion)]
+pub(crate) fn from_output(output: &[widechar]) -> Option<BrailleString> {
+    output
+        .iter()
+        .map(|&c| {
+            BrailleCell::from_louis_dots(c)
+                .or_else(|| std::char::from_u32(u32::from(c)).and_then(BrailleCell::from_unicode))
+        })
+        .collect()
+}
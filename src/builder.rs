@@ -0,0 +1,54 @@
+use std::path::PathBuf;
+
+use super::{Error, Louis, Result, DEFAULT_MAX_OUTPUT_LEN};
+
+/// Configures a `Louis` before initializing it, see `Louis::builder`.
+#[derive(Debug, Clone)]
+pub struct LouisBuilder {
+    table_dirs: Vec<PathBuf>,
+    max_output_len: usize,
+}
+
+impl LouisBuilder {
+    pub(crate) fn new() -> Self {
+        LouisBuilder {
+            table_dirs: Vec::new(),
+            max_output_len: DEFAULT_MAX_OUTPUT_LEN,
+        }
+    }
+
+    /// Adds a directory to search for tables in, see `Louis::set_table_dirs`.
+    pub fn table_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.table_dirs.push(dir.into());
+        self
+    }
+
+    /// Adds several directories to search for tables in, see `Louis::set_table_dirs`.
+    pub fn table_dirs<I>(mut self, dirs: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<PathBuf>,
+    {
+        self.table_dirs.extend(dirs.into_iter().map(Into::into));
+        self
+    }
+
+    /// See `Louis::set_max_output_len`
+    pub fn max_output_len(mut self, max_output_len: usize) -> Self {
+        self.max_output_len = max_output_len;
+        self
+    }
+
+    /// Initializes liblouis with this configuration.
+    ///
+    /// Fails with `Error::Unavailable` if another `Louis` exists, and with `Error::InvalidInput`
+    /// if one of the table directories doesn't exist.
+    pub fn build(self) -> Result<Louis> {
+        let mut louis = Louis::new().ok_or(Error::Unavailable)?;
+        louis.set_max_output_len(self.max_output_len);
+        if !self.table_dirs.is_empty() {
+            louis.set_table_dirs(&self.table_dirs)?;
+        }
+        Ok(louis)
+    }
+}
@@ -0,0 +1,108 @@
+use louis_sys::{
+    formtype, typeforms_computer_braille, typeforms_emph_1, typeforms_emph_2, typeforms_emph_3,
+    typeforms_no_contract, typeforms_no_translate,
+};
+
+use std::ops::Range;
+
+use super::{Error, Result};
+
+/// A kind of emphasis or special treatment that can be applied to a part of the input,
+/// mirroring the `typeforms` enum of liblouis.
+///
+/// Which indicators are emitted for emphasized text depends on the table.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Emphasis {
+    /// Italic text, the same as `Emph(1)`
+    Italic,
+    /// Underlined text, the same as `Emph(2)`
+    Underline,
+    /// Bold text, the same as `Emph(3)`
+    Bold,
+    /// One of the emphasis classes `emph_1` to `emph_10` that a table can define
+    Emph(u8),
+    /// Translate as computer braille
+    ComputerBraille,
+    /// Do not translate, but pass the characters through as dots
+    NoTranslate,
+    /// Translate without contractions
+    NoContract,
+    /// An emphasis class that the table declares by name using `emphclass`, such as `"transnote"`.
+    /// See `Louis::emphasis_classes` for the classes a table defines.
+    Class(String),
+}
+
+impl Emphasis {
+    /// Returns the liblouis `typeform` bit for this kind of emphasis.
+    ///
+    /// The bit for a named `Class` depends on the table, so it has to be looked up
+    /// using `Louis::emphasis_class_typeform` instead.
+    pub fn typeform(&self) -> Result<formtype> {
+        let typeform = match self {
+            Emphasis::Italic => typeforms_emph_1,
+            Emphasis::Underline => typeforms_emph_2,
+            Emphasis::Bold => typeforms_emph_3,
+            Emphasis::Emph(n @ 1..=10) => typeforms_emph_1 << (n - 1),
+            Emphasis::Class(ref class) => {
+                return Err(Error::InvalidInput(format!(
+                    "the typeform of emphasis class \"{}\" depends on the table",
+                    class
+                )))
+            }
+            Emphasis::Emph(n) => {
+                return Err(Error::InvalidInput(format!(
+                    "there is no emphasis class emph_{}",
+                    n
+                )))
+            }
+            Emphasis::ComputerBraille => typeforms_computer_braille,
+            Emphasis::NoTranslate => typeforms_no_translate,
+            Emphasis::NoContract => typeforms_no_contract,
+        };
+        Ok(typeform as formtype)
+    }
+}
+
+/// A span of the input that should be emphasized.
+///
+/// Like the positions in a `Translation`, `range` counts `louis_sys::widechar`s.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EmphasisSpan {
+    pub range: Range<usize>,
+    pub emphasis: Emphasis,
+}
+
+impl EmphasisSpan {
+    pub fn new(range: Range<usize>, emphasis: Emphasis) -> Self {
+        EmphasisSpan { range, emphasis }
+    }
+}
+
+/// Builds the `typeform` array liblouis expects for an input of length `inlen` from a list of spans.
+/// Named emphasis classes are looked up using `class_typeform`.
+pub(crate) fn typeform_buffer<F>(
+    spans: &[EmphasisSpan],
+    inlen: usize,
+    class_typeform: F,
+) -> Result<Vec<formtype>>
+where
+    F: Fn(&str) -> Result<formtype>,
+{
+    let mut typeform = vec![0; inlen];
+    for span in spans {
+        if span.range.start > span.range.end || span.range.end > inlen {
+            return Err(Error::InvalidInput(format!(
+                "emphasis span {:?} is out of bounds for input of length {}",
+                span.range, inlen
+            )));
+        }
+        let bit = match span.emphasis {
+            Emphasis::Class(ref class) => class_typeform(class)?,
+            ref emphasis => emphasis.typeform()?,
+        };
+        for t in &mut typeform[span.range.clone()] {
+            *t |= bit;
+        }
+    }
+    Ok(typeform)
+}
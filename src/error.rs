@@ -0,0 +1,234 @@
+use std::error;
+use std::ffi::NulError;
+use std::fmt;
+use std::io;
+use std::os::raw::{c_int, c_uint};
+use std::path::PathBuf;
+
+/// The ways in which calls into liblouis can fail.
+#[derive(Debug)]
+pub enum Error {
+    /// The table list could not be found or failed to compile.
+    /// Details are also logged by liblouis under the `liblouis` target.
+    InvalidTable(TableCompileError),
+    /// liblouis reported an error during translation, or produced output that is not valid Unicode
+    TranslationFailed,
+    /// The input could not be passed to liblouis, e.g. because it contains an interior NUL character
+    InvalidInput(String),
+    /// liblouis did not translate the entire input because the output buffer was too small
+    OutputTruncated,
+    /// The given translation mode contains bits that liblouis does not know about
+    InvalidMode(c_int),
+    /// The table list does not include a hyphenation dictionary
+    NoHyphenation(String),
+    /// The version reported by liblouis could not be parsed
+    Version(semver::SemVerError),
+    /// A file needed to talk to liblouis could not be accessed
+    Io(io::Error),
+    /// liblouis is already in use by another `Louis`, or a `LouisService` can't take the request:
+    /// its queue is full, or the request was made by one of its own jobs
+    Unavailable,
+    /// The `LouisService` has been shut down
+    ServiceStopped,
+    /// A helper process of a `LouisPool` took longer than its timeout, and was replaced by a new one
+    Timeout,
+    /// A helper process of a `LouisPool` exited while handling a request, e.g. because it ran out of memory,
+    /// and was replaced by a new one. Contains how the process exited.
+    WorkerCrashed(String),
+}
+
+/// A `Result` with [`Error`](enum.Error.html) as its error type.
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidTable(e) => write!(f, "{}", e),
+            Error::TranslationFailed => write!(f, "liblouis failed to translate the input"),
+            Error::InvalidInput(reason) => write!(f, "invalid input: {}", reason),
+            Error::OutputTruncated => write!(f, "translation output was truncated"),
+            Error::InvalidMode(bits) => write!(f, "invalid translation mode {:#x}", bits),
+            Error::NoHyphenation(table_names) => write!(
+                f,
+                "table list \"{}\" has no hyphenation dictionary",
+                table_names
+            ),
+            Error::Version(e) => write!(f, "could not parse liblouis version: {}", e),
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Unavailable => write!(f, "liblouis is already in use"),
+            Error::ServiceStopped => write!(f, "the liblouis service has been shut down"),
+            Error::Timeout => write!(f, "the helper process timed out"),
+            Error::WorkerCrashed(status) => write!(f, "the helper process crashed ({})", status),
+        }
+    }
+}
+
+impl Error {
+    /// Copies the error, for reporting it once for each item of a batch.
+    /// I/O errors can't be copied, so they are recreated from their kind and message.
+    pub(crate) fn duplicate(&self) -> Error {
+        match self {
+            Error::InvalidTable(e) => Error::InvalidTable(e.clone()),
+            Error::TranslationFailed => Error::TranslationFailed,
+            Error::InvalidInput(reason) => Error::InvalidInput(reason.clone()),
+            Error::OutputTruncated => Error::OutputTruncated,
+            Error::InvalidMode(bits) => Error::InvalidMode(*bits),
+            Error::NoHyphenation(table_names) => Error::NoHyphenation(table_names.clone()),
+            Error::Version(e) => Error::Version(e.clone()),
+            Error::Io(e) => Error::Io(io::Error::new(e.kind(), e.to_string())),
+            Error::Unavailable => Error::Unavailable,
+            Error::ServiceStopped => Error::ServiceStopped,
+            Error::Timeout => Error::Timeout,
+            Error::WorkerCrashed(status) => Error::WorkerCrashed(status.clone()),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::InvalidTable(e) => Some(e),
+            Error::Version(e) => Some(e),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<semver::SemVerError> for Error {
+    fn from(e: semver::SemVerError) -> Self {
+        Error::Version(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<NulError> for Error {
+    fn from(e: NulError) -> Self {
+        Error::InvalidInput(format!("NUL byte at position {}", e.nul_position()))
+    }
+}
+
+impl From<widestring::NulError<louis_sys::widechar>> for Error {
+    fn from(e: widestring::NulError<louis_sys::widechar>) -> Self {
+        Error::InvalidInput(format!("NUL character at position {}", e.nul_position()))
+    }
+}
+
+/// How severe a problem liblouis found while compiling a table is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A single problem liblouis reported while compiling a table
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The table file the problem was found in, if liblouis reported one
+    pub file: Option<PathBuf>,
+    /// The line in `file`, counting from 1
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Parses a message liblouis logged at `level`, such as `en_US.tbl:12: error: opcode foo not defined.`
+    /// Returns `None` for messages less severe than warnings.
+    pub(crate) fn parse(level: c_uint, message: &str) -> Option<Self> {
+        let severity = if level >= louis_sys::logLevels_LOG_ERROR {
+            Severity::Error
+        } else if level >= louis_sys::logLevels_LOG_WARN {
+            Severity::Warning
+        } else {
+            return None;
+        };
+        for marker in &["error: ", "warning: "] {
+            if let Some(rest) = message.strip_prefix(marker) {
+                return Some(Diagnostic {
+                    severity,
+                    file: None,
+                    line: None,
+                    message: rest.to_owned(),
+                });
+            }
+            // The file name itself may contain colons, but the line number can't
+            let location_end = match message.find(&format!(": {}", marker)) {
+                Some(end) => end,
+                None => continue,
+            };
+            let mut location = message[..location_end].rsplitn(2, ':');
+            let line = location.next().and_then(|line| line.parse().ok());
+            if let (Some(line), Some(file)) = (line, location.next()) {
+                return Some(Diagnostic {
+                    severity,
+                    file: Some(PathBuf::from(file)),
+                    line: Some(line),
+                    message: message[location_end + 2 + marker.len()..].to_owned(),
+                });
+            }
+        }
+        Some(Diagnostic {
+            severity,
+            file: None,
+            line: None,
+            message: message.to_owned(),
+        })
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    /// Formats the diagnostic the way liblouis logs it
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref file) = self.file {
+            write!(f, "{}:", file.display())?;
+            if let Some(line) = self.line {
+                write!(f, "{}:", line)?;
+            }
+            write!(f, " ")?;
+        }
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}: {}", severity, self.message)
+    }
+}
+
+/// A table list that could not be found or compiled, along with what liblouis reported about it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableCompileError {
+    pub table_names: String,
+    /// The warnings and errors liblouis reported while compiling, in order
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl TableCompileError {
+    /// The diagnostics with `Severity::Error`
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+}
+
+impl fmt::Display for TableCompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "table list \"{}\" could not be compiled",
+            self.table_names
+        )?;
+        for diagnostic in &self.diagnostics {
+            write!(f, "\n  {}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for TableCompileError {}
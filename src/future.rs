@@ -0,0 +1,223 @@
+//! Asynchronous requests to a `LouisService`, for use with tokio.
+
+use std::future::Future;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::thread;
+
+use tokio::sync::oneshot;
+
+use super::modes::{HyphenationMode, TranslationModes};
+use super::service::{Job, Slot, Submitted};
+use super::{Error, Louis, LouisService, Result, Translation, TranslationOptions};
+
+/// A request to a `LouisService` that resolves to its result once the service's thread has handled it.
+///
+/// Requests made while the service's queue is full wait for room without blocking,
+/// and are queued once the future is polled after some has been freed.
+///
+/// Dropping the future before the thread has started on the request cancels it,
+/// and frees its room in the queue right away.
+/// Requests the thread has already started on are finished, but their results are discarded.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate louis;
+/// # extern crate tokio;
+/// # use louis::{Louis, LouisService, modes::TranslationModes};
+/// # use std::sync::atomic::{AtomicUsize, Ordering};
+/// # use std::sync::{mpsc, Arc};
+/// # fn main() {
+/// let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// let service = LouisService::new(Louis::new().unwrap(), 4).unwrap();
+///
+/// // Keep the service's thread busy until `proceed` is sent
+/// let (proceed, wait) = mpsc::channel::<()>();
+/// let busy = service.run_async(move |_| wait.recv().unwrap());
+///
+/// let ran = Arc::new(AtomicUsize::new(0));
+/// let counter = ran.clone();
+/// let cancelled = service.run_async(move |_| counter.fetch_add(1, Ordering::SeqCst));
+/// drop(cancelled);
+///
+/// proceed.send(()).unwrap();
+/// runtime.block_on(busy).unwrap();
+/// let translation = runtime.block_on(service.translate_simple_async(
+///     "en_US.tbl",
+///     "the hand",
+///     false,
+///     TranslationModes::empty(),
+/// ));
+/// assert_eq!(translation.unwrap(), "! h&");
+/// assert_eq!(ran.load(Ordering::SeqCst), 0);
+/// # }
+/// ```
+#[must_use = "requests are cancelled when their future is dropped"]
+pub struct LouisFuture<T> {
+    request: Request,
+    response: oneshot::Receiver<thread::Result<Result<T>>>,
+}
+
+enum Request {
+    /// Waiting for room in the queue
+    Waiting(LouisService, Job),
+    Queued(Arc<Slot>),
+    /// Could not be queued
+    Rejected(Error),
+    /// Only while the future is being polled
+    Done,
+}
+
+impl Request {
+    fn new(service: &LouisService, submitted: Result<Submitted>) -> Self {
+        match submitted {
+            Ok(Submitted::Queued(slot)) => Request::Queued(slot),
+            Ok(Submitted::Full(job)) => Request::Waiting(service.clone(), job),
+            Err(e) => Request::Rejected(e),
+        }
+    }
+}
+
+impl<T> Future for LouisFuture<T> {
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<T>> {
+        let this = self.get_mut();
+        loop {
+            match mem::replace(&mut this.request, Request::Done) {
+                Request::Waiting(service, job) => {
+                    this.request = Request::new(&service, service.poll_submit(job, cx));
+                    if let Request::Waiting(..) = this.request {
+                        return Poll::Pending;
+                    }
+                }
+                Request::Rejected(e) => return Poll::Ready(Err(e)),
+                request => {
+                    this.request = request;
+                    break;
+                }
+            }
+        }
+        match Pin::new(&mut this.response).poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Ok(Ok(result))) => Poll::Ready(result),
+            Poll::Ready(Ok(Err(payload))) => panic::resume_unwind(payload),
+            // The job was dropped without running, because the service was shut down
+            Poll::Ready(Err(_)) => Poll::Ready(Err(Error::ServiceStopped)),
+        }
+    }
+}
+
+impl<T> Drop for LouisFuture<T> {
+    fn drop(&mut self) {
+        if let Request::Queued(ref slot) = self.request {
+            slot.cancel();
+        }
+    }
+}
+
+/// Asynchronous versions of the methods of `LouisService`, available with the `tokio` feature
+impl LouisService {
+    /// Like `run`, but returns a future instead of blocking.
+    pub fn run_async<T, F>(&self, f: F) -> LouisFuture<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Louis) -> T + Send + 'static,
+    {
+        self.request(move |louis| Ok(f(louis)))
+    }
+
+    fn request<T, F>(&self, f: F) -> LouisFuture<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Louis) -> Result<T> + Send + 'static,
+    {
+        let (reply, response) = oneshot::channel();
+        let job: Job = Box::new(move |louis| {
+            let _ = reply.send(panic::catch_unwind(AssertUnwindSafe(|| f(louis))));
+        });
+        LouisFuture {
+            request: Request::new(self, self.try_submit(job)),
+            response,
+        }
+    }
+
+    /// See `Louis::translate_simple`
+    pub fn translate_simple_async(
+        &self,
+        table_names: &str,
+        input: &str,
+        backwards: bool,
+        mode: TranslationModes,
+    ) -> LouisFuture<String> {
+        let (table_names, input) = (table_names.to_owned(), input.to_owned());
+        self.request(move |louis| louis.translate_simple(&table_names, &input, backwards, mode))
+    }
+
+    /// See `Louis::translate`
+    pub fn translate_async(
+        &self,
+        table_names: &str,
+        input: &str,
+        mode: TranslationModes,
+    ) -> LouisFuture<Translation> {
+        let (table_names, input) = (table_names.to_owned(), input.to_owned());
+        self.request(move |louis| louis.translate(&table_names, &input, mode))
+    }
+
+    /// See `Louis::translate_with_options`
+    pub fn translate_with_options_async(
+        &self,
+        table_names: &str,
+        input: &str,
+        mode: TranslationModes,
+        options: &TranslationOptions,
+    ) -> LouisFuture<Translation> {
+        let (table_names, input, options) =
+            (table_names.to_owned(), input.to_owned(), options.clone());
+        self.request(move |louis| {
+            louis.translate_with_options(&table_names, &input, mode, &options)
+        })
+    }
+
+    /// See `Louis::back_translate`
+    pub fn back_translate_async(
+        &self,
+        table_names: &str,
+        input: &str,
+        mode: TranslationModes,
+    ) -> LouisFuture<Translation> {
+        let (table_names, input) = (table_names.to_owned(), input.to_owned());
+        self.request(move |louis| louis.back_translate(&table_names, &input, mode))
+    }
+
+    /// See `Louis::back_translate_with_options`
+    pub fn back_translate_with_options_async(
+        &self,
+        table_names: &str,
+        input: &str,
+        mode: TranslationModes,
+        options: &TranslationOptions,
+    ) -> LouisFuture<Translation> {
+        let (table_names, input, options) =
+            (table_names.to_owned(), input.to_owned(), options.clone());
+        self.request(move |louis| {
+            louis.back_translate_with_options(&table_names, &input, mode, &options)
+        })
+    }
+
+    /// See `Louis::hyphenate`
+    pub fn hyphenate_async(
+        &self,
+        table_names: &str,
+        word: &str,
+        mode: HyphenationMode,
+    ) -> LouisFuture<Vec<usize>> {
+        let (table_names, word) = (table_names.to_owned(), word.to_owned());
+        self.request(move |louis| louis.hyphenate(&table_names, &word, mode))
+    }
+}
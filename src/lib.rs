@@ -1,20 +1,102 @@
-extern crate widestring;
+extern crate libc;
 extern crate louis_sys;
+extern crate widestring;
+#[macro_use]
+extern crate bitflags;
 extern crate semver;
 #[macro_use]
 extern crate log;
+#[cfg(feature = "tokio")]
+extern crate tokio;
 
 use louis_sys::ThreadUnsafetyToken;
-use std::cell::Cell;
+use metadata::{Dots, LanguageTag, Level, TableChoice, TableInfo, TableQuery};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::ffi::{CStr, CString};
 use std::marker::PhantomData;
 use std::os::raw::{c_char, c_int, c_uint};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
+pub mod braille;
+mod builder;
+pub mod emphasis;
+mod error;
+#[cfg(feature = "tokio")]
+mod future;
+pub mod metadata;
 pub mod modes;
+mod pool;
+pub mod process;
+mod resolver;
+mod service;
+mod table;
+
+pub use builder::LouisBuilder;
+pub use error::{Diagnostic, Error, Result, Severity, TableCompileError};
+#[cfg(feature = "tokio")]
+pub use future::LouisFuture;
+pub use pool::{LouisPool, LouisPoolBuilder};
+pub use resolver::{TableResolver, TableSource};
+pub use service::LouisService;
+pub use table::Table;
 
 type LouisString = widestring::UCString<louis_sys::widechar>;
-const OUTLEN_MULTIPLIER: c_int = 4 + 2 * std::mem::size_of::<louis_sys::widechar>() as c_int;
+const OUTLEN_MULTIPLIER: usize = 4 + 2 * std::mem::size_of::<louis_sys::widechar>();
+
+/// The default upper bound on the length of a translation's output, see `Louis::set_max_output_len`.
+pub const DEFAULT_MAX_OUTPUT_LEN: usize = 1 << 24;
+
+/// Rules passed to `Table::with_rules` must be shorter than this many bytes, a limit imposed by liblouis.
+pub const MAX_RULE_LEN: usize = 2048;
+
+/// Words passed to `Louis::hyphenate` must be shorter than this, a limit imposed by liblouis.
+pub const MAX_HYPHENATION_LEN: usize = 100;
+
+/// The result of a translation, including the mappings between input and output positions.
+///
+/// Positions are indices into the text as liblouis sees it, i.e. in units of
+/// `louis_sys::widechar`. For the bundled UCS-4 build of liblouis, these are simply `char`s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Translation {
+    /// The translated text
+    pub output: String,
+    /// The output as braille cells, if it was translated to dots using `TranslationModes::DOTS_IO`.
+    /// This doesn't depend on whether `UC_BRL` was used, and is `None` for back-translations.
+    pub braille: Option<braille::BrailleString>,
+    /// For each input character, the position of the output character it was translated to.
+    ///
+    /// Input characters that produced no output, e.g. undefined dots dropped because of
+    /// `TranslationModes::NO_UNDEFINED_DOTS`, map to where their output would have been:
+    /// the position of the next output character, or the length of the output.
+    pub output_pos: Vec<usize>,
+    /// For each output character, the position of the input character it was translated from
+    pub input_pos: Vec<usize>,
+    /// The position of the cursor in the output, if a cursor was given in the `TranslationOptions`.
+    /// A cursor on input that produced no output is placed like in `output_pos`.
+    pub cursor: Option<usize>,
+    /// The positions in the output before which a line may be broken,
+    /// if hyphenation points were given in the `TranslationOptions`
+    pub hyphens: Option<Vec<usize>>,
+}
+
+/// Optional inputs to a translation, see `Louis::translate_with_options`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TranslationOptions {
+    /// Parts of the input that should be emphasized
+    pub emphasis: Vec<emphasis::EmphasisSpan>,
+    /// The position of the cursor in the input.
+    ///
+    /// Its position in the output is returned in `Translation::cursor`. With the modes
+    /// `COMPBRL_AT_CURSOR` or `COMPBRL_LEFT_CURSOR`, the word under the cursor is shown
+    /// in computer braille. A cursor at the very end of the input is allowed, but isn't on a word.
+    pub cursor: Option<usize>,
+    /// The positions in the input before which a word may be hyphenated, as returned by `Louis::hyphenate`.
+    ///
+    /// When given, the corresponding break points in the braille are returned in `Translation::hyphens`.
+    pub hyphens: Option<Vec<usize>>,
+}
 
 /// A singleton that handles all access to liblouis.
 ///
@@ -29,8 +111,17 @@ const OUTLEN_MULTIPLIER: c_int = 4 + 2 * std::mem::size_of::<louis_sys::widechar
 pub struct Louis {
     _token: ThreadUnsafetyToken,
     nosync: PhantomData<Cell<u8>>,
+    max_output_len: usize,
+    /// The directories set using `set_table_dirs`
+    table_dirs: Vec<PathBuf>,
+    /// Table lists compiled by `compile_with_rules`, by base table list and rules
+    overlays: RefCell<HashMap<(String, Vec<String>), CString>>,
 }
 
+/// liblouis copies the data path into a buffer of this size,
+/// and later combines it with `LOUIS_TABLEPATH` into another one
+const MAX_DATA_PATH_LEN: usize = 2048 - 256;
+
 impl Louis {
     /// Tries to initialize liblouis, returning `Some(Louis)` on success.
     /// On failure, it returns `None`, indicating that the ThreadUnsafetyToken has already been taken.
@@ -39,50 +130,521 @@ impl Louis {
             let louis = Louis {
                 _token,
                 nosync: PhantomData,
+                max_output_len: DEFAULT_MAX_OUTPUT_LEN,
+                table_dirs: Vec::new(),
+                overlays: RefCell::new(HashMap::new()),
             };
             louis.configure_logging();
             louis
         })
     }
 
+    /// Returns a `LouisBuilder` to configure liblouis before initializing it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use louis::Louis;
+    /// # let tables = std::env::temp_dir();
+    /// let louis = Louis::builder()
+    ///     .table_dir(&tables)
+    ///     .max_output_len(1 << 16)
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(louis.max_output_len(), 1 << 16);
+    /// ```
+    pub fn builder() -> LouisBuilder {
+        LouisBuilder::new()
+    }
+
+    /// Returns the maximum number of characters a single translation may produce.
+    pub fn max_output_len(&self) -> usize {
+        self.max_output_len
+    }
+
+    /// Sets the maximum number of characters a single translation may produce.
+    ///
+    /// Output buffers start out at a small multiple of the input length and are grown
+    /// whenever liblouis runs out of space. Translations whose output would be longer
+    /// than `max_output_len` fail with `Error::OutputTruncated`.
+    pub fn set_max_output_len(&mut self, max_output_len: usize) {
+        self.max_output_len = max_output_len;
+    }
+
+    /// Returns the data path set using `set_data_path`, if any.
+    ///
+    /// While table directories are set using `set_table_dirs`, liblouis' data path holds
+    /// a list of them rather than a single path, so this returns `None`; see `table_dirs`.
+    pub fn data_path(&self) -> Option<PathBuf> {
+        if !self.table_dirs.is_empty() {
+            return None;
+        }
+        let path = unsafe { louis_sys::lou_getDataPath() };
+        if path.is_null() {
+            None
+        } else {
+            let path = unsafe { CStr::from_ptr(path) }.to_string_lossy();
+            Some(PathBuf::from(&*path))
+        }
+    }
+
+    /// Sets the data path of liblouis. Tables are then also searched in `liblouis/tables` below `path`,
+    /// after the directories in the `LOUIS_TABLEPATH` environment variable.
+    ///
+    /// Tables that have already been compiled are not affected. The data path is reset when `Louis` is dropped.
+    pub fn set_data_path<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        check_dir(path)?;
+        self.set_raw_data_path(path_str(path)?)?;
+        self.table_dirs.clear();
+        Ok(())
+    }
+
+    /// Returns the directories set using `set_table_dirs`, in search order.
+    pub fn table_dirs(&self) -> &[PathBuf] {
+        &self.table_dirs
+    }
+
+    /// Makes liblouis search for tables directly in each of `dirs`, in order,
+    /// after the directories in the `LOUIS_TABLEPATH` environment variable.
+    ///
+    /// This replaces the data path set using `set_data_path`.
+    /// Tables that have already been compiled are not affected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use louis::{Louis, Error};
+    /// let mut louis = Louis::new().unwrap();
+    /// match louis.set_table_dirs(&["/no/such/dir"]) {
+    ///     Err(Error::InvalidInput(_)) => (),
+    ///     other => panic!("unexpected result: {:?}", other),
+    /// }
+    /// ```
+    pub fn set_table_dirs<P: AsRef<Path>>(&mut self, dirs: &[P]) -> Result<()> {
+        let mut dir_strs = Vec::with_capacity(dirs.len() + 1);
+        for dir in dirs {
+            let dir = dir.as_ref();
+            check_dir(dir)?;
+            let dir_str = path_str(dir)?;
+            if dir_str.contains(',') {
+                return Err(Error::InvalidInput(format!(
+                    "table directory {} contains a comma",
+                    dir.display()
+                )));
+            }
+            dir_strs.push(dir_str);
+        }
+        match dir_strs.first().cloned() {
+            // liblouis splits the search path at commas, and appends liblouis/tables only to the last entry.
+            // Repeating the first directory there keeps the others as they are.
+            Some(first) => {
+                dir_strs.push(first);
+                self.set_raw_data_path(&dir_strs.join(","))?;
+            }
+            None => {
+                unsafe { louis_sys::lou_setDataPath(std::ptr::null()) };
+                self.reset_table_index();
+            }
+        }
+        self.table_dirs = dirs.iter().map(|dir| dir.as_ref().to_owned()).collect();
+        Ok(())
+    }
+
+    fn set_raw_data_path(&mut self, path: &str) -> Result<()> {
+        let env_len = std::env::var_os("LOUIS_TABLEPATH").map_or(0, |path| path.len());
+        if path.len() + env_len >= MAX_DATA_PATH_LEN {
+            return Err(Error::InvalidInput(format!(
+                "data path and LOUIS_TABLEPATH must be shorter than {} bytes together",
+                MAX_DATA_PATH_LEN
+            )));
+        }
+        let path = CString::new(path)?;
+        unsafe { louis_sys::lou_setDataPath(path.as_ptr()) };
+        self.reset_table_index();
+        Ok(())
+    }
+
+    /// Makes liblouis ask `resolver` which files table names refer to, both for the table names
+    /// passed to `Louis` and for the `include` statements in tables.
+    ///
+    /// Panics and errors in `resolver` make the table fail to compile,
+    /// and are reported as diagnostics of the resulting `Error::InvalidTable`.
+    /// Tables that have already been compiled are not resolved again.
+    /// The resolver is removed when `Louis` is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use louis::{Louis, TableSource, modes::TranslationModes};
+    /// let mut louis = Louis::new().unwrap();
+    /// louis.set_table_resolver(|tables: &str, _base: Option<&str>| match tables {
+    ///     "my-g1.utb" => Some(vec![TableSource::Contents {
+    ///         name: tables.to_owned(),
+    ///         contents: b"include en-ueb-g1.ctb\nword hello 1-12-14\n".to_vec(),
+    ///     }]),
+    ///     // Fall back to the default resolver, e.g. for the include above
+    ///     _ => None,
+    /// });
+    /// let table = louis.load_table("my-g1.utb").unwrap();
+    /// assert_eq!(table.translate("hello", TranslationModes::empty()).unwrap().output, "abc");
+    /// louis.reset_table_resolver();
+    /// ```
+    pub fn set_table_resolver<R: TableResolver + 'static>(&mut self, resolver: R) {
+        resolver::register(Box::new(resolver));
+    }
+
+    /// Restores liblouis' default table resolver.
+    pub fn reset_table_resolver(&mut self) {
+        resolver::unregister();
+    }
+
     /// Returns the version of liblouis that this crate is linked against
-    pub fn version(&self) -> Result<semver::Version, semver::SemVerError> {
-        let version_str = unsafe { CStr::from_ptr(louis_sys::lou_version()) }
-            .to_str()
-            .unwrap();
-        semver::Version::parse(version_str)
+    pub fn version(&self) -> Result<semver::Version> {
+        let version_str = unsafe { CStr::from_ptr(louis_sys::lou_version()) }.to_string_lossy();
+        Ok(semver::Version::parse(&version_str)?)
+    }
+
+    /// Lists all indexed tables along with their metadata, see `index_tables`.
+    ///
+    /// Tables are listed in the order of their names. To list them by language and grade instead,
+    /// see `TableInfo::cmp_by_language_and_grade` and `metadata::group_by_language`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use louis::Louis;
+    /// let louis = Louis::new().unwrap();
+    /// let tables = louis.list_tables().unwrap();
+    /// let table = tables
+    ///     .iter()
+    ///     .find(|info| info.path.file_name().unwrap() == "en-ueb-g2.ctb")
+    ///     .unwrap();
+    /// assert_eq!(table.display_name.as_deref(), Some("English (UEB) contracted braille"));
+    /// assert_eq!(table.name.as_deref(), Some("Unified English braille, grade 2"));
+    /// ```
+    pub fn list_tables(&self) -> Result<Vec<TableInfo>> {
+        Ok(self
+            .indexed_tables()
+            .iter()
+            .filter_map(|table| match self.table_info_c(table) {
+                Ok(info) => Some(info),
+                Err(e) => {
+                    // The table was readable when it was indexed
+                    warn!("skipping indexed table {:?}: {}", table, e);
+                    None
+                }
+            })
+            .collect())
     }
 
-    /// Lists the filenames of all the tables that are available
-    pub fn list_tables(&self) -> Vec<String> {
+    /// Returns the names of the indexed tables, see `index_tables`.
+    fn indexed_tables(&self) -> Vec<CString> {
         let list_begin = unsafe { louis_sys::lou_listTables() };
         let mut res = Vec::new();
+        if list_begin.is_null() {
+            return res;
+        }
         for offset in 0.. {
             let ptr = unsafe { *(list_begin.offset(offset)) };
             if ptr.is_null() {
                 break;
             }
-            let table_name = Path::new(unsafe { CStr::from_ptr(ptr) }.to_str().unwrap())
-                .file_name()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_owned();
-            res.push(table_name);
+            res.push(unsafe { CStr::from_ptr(ptr) }.to_owned());
+            unsafe { libc::free(ptr as *mut libc::c_void) };
         }
+        unsafe { libc::free(list_begin as *mut libc::c_void) };
         res
     }
 
-    /// Translates the text in `input` according to the tables given by `table_names`
+    /// Reads the metadata of `table`, which has to resolve to a single file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use louis::{Louis, metadata::{Contraction, TableType}};
+    /// let louis = Louis::new().unwrap();
+    /// let info = louis.table_info("en-ueb-g2.ctb").unwrap();
+    /// assert!(info.path.ends_with("en-ueb-g2.ctb"));
+    /// assert_eq!(info.locale.unwrap().as_str(), "en");
+    /// assert_eq!(info.grade, Some(2.0));
+    /// assert_eq!(info.contraction, Some(Contraction::Full));
+    /// assert_eq!(info.table_type, Some(TableType::Literary));
+    /// assert_eq!(info.display_name.as_deref(), Some("English (UEB) contracted braille"));
+    /// ```
+    pub fn table_info(&self, table: &str) -> Result<TableInfo> {
+        self.table_info_c(&CString::new(table)?)
+    }
+
+    fn table_info_c(&self, table: &CStr) -> Result<TableInfo> {
+        let table_name = table.to_string_lossy().into_owned();
+        let (info, mut diagnostics) = self.capture_diagnostics(|| {
+            let path = resolve_single_table(table)?;
+            Some(TableInfo::new(table_name.clone(), path, |key| {
+                let key = CString::new(key).unwrap();
+                let value = unsafe { louis_sys::lou_getTableInfo(table.as_ptr(), key.as_ptr()) };
+                if value.is_null() {
+                    return None;
+                }
+                let value_str = unsafe { CStr::from_ptr(value) }
+                    .to_string_lossy()
+                    .into_owned();
+                unsafe { libc::free(value as *mut libc::c_void) };
+                Some(value_str)
+            }))
+        });
+        // The table is read once per field, repeating any problems
+        let mut seen = HashSet::new();
+        diagnostics.retain(|diagnostic| seen.insert(diagnostic.clone()));
+        match info {
+            Some(info)
+                if diagnostics
+                    .iter()
+                    .all(|diagnostic| diagnostic.severity != Severity::Error) =>
+            {
+                Ok(info)
+            }
+            _ => Err(Error::InvalidTable(TableCompileError {
+                table_names: table_name,
+                diagnostics,
+            })),
+        }
+    }
+
+    /// Returns the table that best matches `query`, if any table matches at all.
+    ///
+    /// Only indexed tables are searched, see `index_tables`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use louis::{Louis, metadata::{Contraction, TableQuery}};
+    /// let louis = Louis::new().unwrap();
+    /// let query = TableQuery::new()
+    ///     .locale("en-US".parse().unwrap())
+    ///     .contraction(Contraction::Full)
+    ///     .grade(2.0);
+    /// let info = louis.find_table(&query).unwrap().unwrap();
+    /// assert!(info.table.ends_with("en_US.tbl"));
+    /// ```
+    pub fn find_table(&self, query: &TableQuery) -> Result<Option<TableInfo>> {
+        let query = CString::new(query.to_query_string()?)?;
+        let found = unsafe { louis_sys::lou_findTable(query.as_ptr()) };
+        if found.is_null() {
+            return Ok(None);
+        }
+        // Table names may not be valid UTF-8, so they are passed on as they are
+        let table = unsafe { CStr::from_ptr(found) }.to_owned();
+        unsafe { libc::free(found as *mut libc::c_void) };
+        self.table_info_c(&table).map(Some)
+    }
+
+    /// Returns all tables that match `query`, the best match first.
+    ///
+    /// Only indexed tables are searched, see `index_tables`.
+    pub fn find_tables(&self, query: &TableQuery) -> Result<Vec<TableInfo>> {
+        let query = CString::new(query.to_query_string()?)?;
+        let list_begin = unsafe { louis_sys::lou_findTables(query.as_ptr()) };
+        let mut tables = Vec::new();
+        if list_begin.is_null() {
+            return Ok(Vec::new());
+        }
+        for offset in 0.. {
+            let ptr = unsafe { *(list_begin.offset(offset)) };
+            if ptr.is_null() {
+                break;
+            }
+            tables.push(unsafe { CStr::from_ptr(ptr) }.to_owned());
+            unsafe { libc::free(ptr as *mut libc::c_void) };
+        }
+        unsafe { libc::free(list_begin as *mut libc::c_void) };
+        tables
+            .iter()
+            .map(|table| self.table_info_c(table))
+            .collect()
+    }
+
+    /// Picks the indexed table that best fits a locale and level of contraction, and optionally a number of dots.
+    ///
+    /// Locales fall back as in BCP 47 lookup, so `de-CH` falls back to `de` if no table fits `de-CH`.
+    /// See `tables_for` for how tables are ranked.
     ///
     /// # Examples
     ///
-    /// Pass `mode=0` for regular translation:
+    /// ```
+    /// # use louis::{Louis, metadata::Contraction};
+    /// let louis = Louis::new().unwrap();
+    /// let locale = "de-AT".parse().unwrap();
+    /// let choice = louis.table_for(&locale, Contraction::Full, None).unwrap().unwrap();
+    /// assert!(choice.info.table.ends_with("de.tbl"));
+    /// assert_eq!(
+    ///     choice.reasons,
+    ///     ["no table for de-AT fits, fell back to locale de", "contraction full matches"]
+    /// );
+    /// ```
+    pub fn table_for<L: Into<Level>>(
+        &self,
+        locale: &LanguageTag,
+        level: L,
+        dots: Option<Dots>,
+    ) -> Result<Option<TableChoice>> {
+        Ok(self.tables_for(locale, level, dots)?.into_iter().next())
+    }
+
+    /// Returns every indexed table that fits a locale and level of contraction, and optionally a number of dots,
+    /// the best fit first.
+    ///
+    /// Tables are ranked by how close their locale is to `locale`, then by whether they declare `level`
+    /// rather than just implying it, then by whether they declare `dots`. Ties are broken in favour of literary
+    /// over computer braille tables, of tables that can translate in both directions, and finally by name.
+    pub fn tables_for<L: Into<Level>>(
+        &self,
+        locale: &LanguageTag,
+        level: L,
+        dots: Option<Dots>,
+    ) -> Result<Vec<TableChoice>> {
+        Ok(metadata::rank(
+            self.list_tables()?,
+            locale,
+            level.into(),
+            dots,
+        ))
+    }
+
+    /// Replaces the tables `find_table`, `find_tables` and `list_tables` search with `tables`,
+    /// each of which has to resolve to a single file. Tables without metadata are left out.
+    ///
+    /// By default, and after calling this with an empty list, every file on the table search path is indexed.
+    /// Changing the data path or the table directories resets the index.
+    pub fn index_tables<S: AsRef<str>>(&mut self, tables: &[S]) -> Result<()> {
+        if tables.is_empty() {
+            self.reset_table_index();
+            return Ok(());
+        }
+        let tables = tables
+            .iter()
+            .map(|table| CString::new(table.as_ref()))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let mut pointers: Vec<*const c_char> = tables
+            .iter()
+            .map(|table| table.as_ptr())
+            .chain(Some(std::ptr::null()))
+            .collect();
+        unsafe { louis_sys::lou_indexTables(pointers.as_mut_ptr()) };
+        Ok(())
+    }
+
+    /// Makes liblouis index the table search path again the next time the index is needed.
+    fn reset_table_index(&self) {
+        // Indexing no tables clears the index, but warns about it
+        let configured = filter_to_lou_loglevel(log::STATIC_MAX_LEVEL);
+        let mut no_tables = [std::ptr::null()];
+        unsafe {
+            louis_sys::lou_setLogLevel(louis_sys::logLevels_LOG_OFF);
+            louis_sys::lou_indexTables(no_tables.as_mut_ptr());
+            louis_sys::lou_setLogLevel(configured);
+        };
+    }
+
+    /// Compiles the tables in `table_names` and checks them for errors, returning a `Table`
+    /// that can be used to translate without having to compile or look up the tables again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use louis::{Louis, Error, modes::TranslationModes};
+    /// let louis = Louis::new().unwrap();
+    /// let table = louis.load_table("en_US.tbl").unwrap();
+    /// let translation = table.translate("A bird in the hand", TranslationModes::empty()).unwrap();
+    /// assert_eq!(translation.output, ",a bird 9 ! h&");
+    ///
+    /// match louis.load_table("en_US.tbl,no_such_table.utb") {
+    ///     Err(Error::InvalidTable(_)) => (),
+    ///     other => panic!("unexpected result: {:?}", other.map(|table| table.names().to_owned())),
+    /// }
+    /// ```
+    pub fn load_table(&self, table_names: &str) -> Result<Table<'_>> {
+        let table_names = CString::new(table_names)?;
+        self.check_table(&table_names)?;
+        Ok(Table::new(self, table_names))
+    }
+
+    /// Lists the names of the emphasis classes defined by the tables in `table_names`,
+    /// in the order of their `emphclass` declarations.
+    ///
+    /// # Examples
     ///
     /// ```
     /// # use louis::Louis;
     /// let louis = Louis::new().unwrap();
-    /// let brl = louis.translate_simple("ru.tbl", "Я понимаю", false, 0);
+    /// let classes = louis.emphasis_classes("en-ueb-g2.ctb").unwrap();
+    /// assert_eq!(classes[..5], ["italic", "underline", "bold", "script", "transnote"]);
+    /// ```
+    pub fn emphasis_classes(&self, table_names: &str) -> Result<Vec<String>> {
+        let table_names = CString::new(table_names)?;
+        self.check_table(&table_names)?;
+        let list_begin = unsafe { louis_sys::lou_getEmphClasses(table_names.as_ptr()) };
+        if list_begin.is_null() {
+            return Err(Error::TranslationFailed);
+        }
+        let mut res = Vec::new();
+        for offset in 0.. {
+            let ptr = unsafe { *(list_begin.offset(offset)) };
+            if ptr.is_null() {
+                break;
+            }
+            res.push(
+                unsafe { CStr::from_ptr(ptr) }
+                    .to_string_lossy()
+                    .into_owned(),
+            );
+        }
+        // Only the array itself is allocated for us, the names belong to the table
+        unsafe { libc::free(list_begin as *mut libc::c_void) };
+        Ok(res)
+    }
+
+    /// Returns the `typeform` bit that the tables in `table_names` use for the emphasis class `class`.
+    ///
+    /// ```
+    /// # use louis::Louis;
+    /// # let louis = Louis::new().unwrap();
+    /// assert_eq!(louis.emphasis_class_typeform("en-ueb-g2.ctb", "transnote").unwrap(), 0x10);
+    /// ```
+    pub fn emphasis_class_typeform(
+        &self,
+        table_names: &str,
+        class: &str,
+    ) -> Result<louis_sys::formtype> {
+        let table_names = CString::new(table_names)?;
+        self.class_typeform(&table_names, class)
+    }
+
+    fn class_typeform(&self, table_names: &CStr, class: &str) -> Result<louis_sys::formtype> {
+        self.check_table(table_names)?;
+        let class_c = CString::new(class)?;
+        match unsafe {
+            louis_sys::lou_getTypeformForEmphClass(table_names.as_ptr(), class_c.as_ptr())
+        } {
+            0 => Err(Error::InvalidInput(format!(
+                "table list \"{}\" does not define emphasis class \"{}\"",
+                table_names.to_string_lossy(),
+                class
+            ))),
+            typeform => Ok(typeform),
+        }
+    }
+
+    /// Translates the text in `input` according to the tables given by `table_names`
+    ///
+    /// # Examples
+    ///
+    /// Pass an empty `mode` for regular translation:
+    ///
+    /// ```
+    /// # use louis::{Louis, modes::TranslationModes};
+    /// let louis = Louis::new().unwrap();
+    /// let brl = louis.translate_simple("ru.tbl", "Я понимаю", false, TranslationModes::empty()).unwrap();
     /// assert_eq!(brl, "$ PONIMA|");
     /// ```
     ///
@@ -91,73 +653,843 @@ impl Louis {
     /// ```
     /// # use louis::{Louis, modes::DOTS_UNICODE};
     /// # let louis = Louis::new().unwrap();
-    /// let dots = louis.translate_simple("sr.tbl", "Добродошли", false, DOTS_UNICODE);
+    /// let dots = louis.translate_simple("sr.tbl", "Добродошли", false, DOTS_UNICODE).unwrap();
     /// assert_eq!(dots, "⠨⠙⠕⠃⠗⠕⠙⠕⠱⠇⠊");
     /// ```
     ///
     /// Pass `backwards=true` for backtranslation:
     ///
     /// ```
-    /// # use louis::Louis;
+    /// # use louis::{Louis, modes::TranslationModes};
     /// # let louis = Louis::new().unwrap();
     /// let dots = "⠠⠭ ⠐⠺⠎⠖";
-    /// let txt = louis.translate_simple("en_US.tbl", dots, true, 0);
+    /// let txt = louis.translate_simple("en_US.tbl", dots, true, TranslationModes::empty()).unwrap();
     /// assert_eq!(txt, "It works!");
     /// ```
     ///
     /// To use multiple tables, pass them as a comma-separated list:
     ///
     /// ```
-    /// # use louis::Louis;
+    /// # use louis::{Louis, modes::TranslationModes};
     /// # let louis = Louis::new().unwrap();
     /// let txt = "This is another way to make dots.";
-    /// let dots = louis.translate_simple("unicode.dis,en_US.tbl", txt, false, 0);
+    /// let dots = louis.translate_simple("unicode.dis,en_US.tbl", txt, false, TranslationModes::empty()).unwrap();
     /// assert_eq!(dots, "⠠⠹ ⠊⠎ ⠁⠝⠕⠮⠗ ⠺⠁⠽ ⠖⠍⠁⠅⠑ ⠙⠕⠞⠎⠲");
     /// ```
     ///
+    /// Tables that cannot be found result in an error:
+    ///
+    /// ```
+    /// # use louis::{Louis, Error, modes::TranslationModes};
+    /// # let louis = Louis::new().unwrap();
+    /// match louis.translate_simple("no_such_table.utb", "Hello", false, TranslationModes::empty()) {
+    ///     Err(Error::InvalidTable(_)) => (),
+    ///     other => panic!("unexpected result: {:?}", other),
+    /// }
+    /// ```
     pub fn translate_simple(
         &self,
         table_names: &str,
         input: &str,
         backwards: bool,
         mode: modes::TranslationModes,
-    ) -> String {
-        let table_names = CString::new(table_names).unwrap();
-        let inbuf = LouisString::from_str(input).unwrap();
-        let mut inlen = inbuf.len() as c_int;
+    ) -> Result<String> {
+        let table_names = CString::new(table_names)?;
+        let inbuf = LouisString::from_str(input)?;
 
-        let mut outlen = inlen * OUTLEN_MULTIPLIER;
-        let mut outvec = Vec::with_capacity(outlen as usize);
-        let outptr = outvec.as_mut_ptr();
+        let outvec = self.with_output_buffer(&table_names, inbuf.len(), |capacity| {
+            let mut inlen = inbuf.len() as c_int;
+            let mut outlen = capacity as c_int;
+            let mut outvec = Vec::with_capacity(capacity);
 
-        unsafe {
-            if backwards {
-                louis_sys::lou_backTranslateString(
-                    table_names.as_ptr(),
-                    inbuf.as_ptr(),
-                    &mut inlen as *mut _,
-                    outptr,
-                    &mut outlen as *mut _,
-                    std::ptr::null_mut::<louis_sys::formtype>(),
-                    std::ptr::null_mut::<c_char>(),
-                    mode,
-                );
-            } else {
-                louis_sys::lou_translateString(
-                    table_names.as_ptr(),
-                    inbuf.as_ptr(),
-                    &mut inlen as *mut _,
-                    outptr,
-                    &mut outlen as *mut _,
-                    std::ptr::null_mut::<louis_sys::formtype>(),
-                    std::ptr::null_mut::<c_char>(),
+            let success = unsafe {
+                if backwards {
+                    louis_sys::lou_backTranslateString(
+                        table_names.as_ptr(),
+                        inbuf.as_ptr(),
+                        &mut inlen as *mut _,
+                        outvec.as_mut_ptr(),
+                        &mut outlen as *mut _,
+                        std::ptr::null_mut::<louis_sys::formtype>(),
+                        std::ptr::null_mut::<c_char>(),
+                        mode.bits(),
+                    )
+                } else {
+                    louis_sys::lou_translateString(
+                        table_names.as_ptr(),
+                        inbuf.as_ptr(),
+                        &mut inlen as *mut _,
+                        outvec.as_mut_ptr(),
+                        &mut outlen as *mut _,
+                        std::ptr::null_mut::<louis_sys::formtype>(),
+                        std::ptr::null_mut::<c_char>(),
+                        mode.bits(),
+                    )
+                }
+            };
+            unsafe { outvec.set_len(outlen as usize) };
+            Attempt {
+                success,
+                consumed: inlen,
+                written: outlen,
+                output: outvec,
+            }
+        })?;
+
+        from_louis_string(outvec)
+    }
+
+    /// Translates the text in `input` according to the tables given by `table_names`,
+    /// also returning how the positions in the input and output relate to each other.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use louis::{Louis, modes::TranslationModes};
+    /// let louis = Louis::new().unwrap();
+    /// let translation = louis.translate("en_US.tbl", "A bird in the hand", TranslationModes::empty()).unwrap();
+    /// assert_eq!(translation.output, ",a bird 9 ! h&");
+    ///
+    /// // "the" is contracted to a single cell
+    /// assert_eq!(translation.output_pos[10..13], [10, 10, 10]);
+    /// assert_eq!(translation.input_pos[10], 10);
+    /// ```
+    pub fn translate(
+        &self,
+        table_names: &str,
+        input: &str,
+        mode: modes::TranslationModes,
+    ) -> Result<Translation> {
+        self.translate_with_options(table_names, input, mode, &TranslationOptions::default())
+    }
+
+    /// Like `translate`, but also takes `TranslationOptions` such as emphasis.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use louis::{Louis, TranslationOptions, modes::TranslationModes};
+    /// use louis::emphasis::{Emphasis, EmphasisSpan};
+    /// let louis = Louis::new().unwrap();
+    /// let options = TranslationOptions {
+    ///     emphasis: vec![EmphasisSpan::new(8..12, Emphasis::Bold)],
+    ///     ..Default::default()
+    /// };
+    /// let translation = louis
+    ///     .translate_with_options("en-ueb-g1.ctb", "This is bold", TranslationModes::empty(), &options)
+    ///     .unwrap();
+    /// assert_eq!(translation.output, ",this is ~1bold");
+    /// ```
+    ///
+    /// Passing a cursor shows where it ends up in the output:
+    ///
+    /// ```
+    /// # use louis::{Louis, TranslationOptions, modes::TranslationModes};
+    /// # let louis = Louis::new().unwrap();
+    /// let options = TranslationOptions {
+    ///     cursor: Some(14),
+    ///     ..Default::default()
+    /// };
+    /// let input = "A bird in the hand";
+    /// let contracted = louis
+    ///     .translate_with_options("en_US.tbl", input, TranslationModes::empty(), &options)
+    ///     .unwrap();
+    /// assert_eq!(contracted.output, ",a bird 9 ! h&");
+    /// assert_eq!(contracted.cursor, Some(12));
+    ///
+    /// // Show the word under the cursor uncontracted
+    /// let expanded = louis
+    ///     .translate_with_options("en_US.tbl", input, TranslationModes::COMPBRL_AT_CURSOR, &options)
+    ///     .unwrap();
+    /// assert_eq!(expanded.output, ",a bird 9 ! hand");
+    /// ```
+    ///
+    /// Hyphenation points in the input are mapped to break points in the braille:
+    ///
+    /// ```
+    /// # use louis::{Louis, TranslationOptions, modes::{HyphenationMode, TranslationModes}};
+    /// # let louis = Louis::new().unwrap();
+    /// let options = TranslationOptions {
+    ///     hyphens: Some(vec![2, 6]), // hy-phen-ation
+    ///     ..Default::default()
+    /// };
+    /// let translation = louis
+    ///     .translate_with_options("en_US.tbl", "hyphenation", TranslationModes::empty(), &options)
+    ///     .unwrap();
+    /// assert_eq!(translation.output, "hyph5,n");
+    /// assert_eq!(translation.hyphens, Some(vec![2, 5]));
+    /// ```
+    pub fn translate_with_options(
+        &self,
+        table_names: &str,
+        input: &str,
+        mode: modes::TranslationModes,
+        options: &TranslationOptions,
+    ) -> Result<Translation> {
+        self.translate_positions(&CString::new(table_names)?, input, false, mode, options)
+    }
+
+    /// Translates each of `inputs` like `translate`, e.g. the paragraphs of a book.
+    ///
+    /// This is faster than translating the inputs one at a time: the tables are looked up only once,
+    /// and the buffers passed to liblouis are reused from one input to the next.
+    /// An input that fails to translate doesn't affect the others. To translate in several
+    /// processes at once, use `LouisPool::translate_batch`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use louis::{Louis, modes::TranslationModes};
+    /// let louis = Louis::new().unwrap();
+    /// let paragraphs = ["A bird in the hand", "is worth two in the bush."];
+    /// let translations = louis.translate_batch("en_US.tbl", &paragraphs, TranslationModes::empty());
+    /// assert_eq!(translations.len(), 2);
+    /// assert_eq!(translations[0].as_ref().unwrap().output, ",a bird 9 ! h&");
+    /// ```
+    pub fn translate_batch<S: AsRef<str>>(
+        &self,
+        table_names: &str,
+        inputs: &[S],
+        mode: modes::TranslationModes,
+    ) -> Vec<Result<Translation>> {
+        match self.load_table(table_names) {
+            Ok(table) => table.translate_batch(inputs, mode),
+            Err(e) => inputs.iter().map(|_| Err(e.duplicate())).collect(),
+        }
+    }
+
+    pub(crate) fn translate_batch_compiled<S: AsRef<str>>(
+        &self,
+        table_names: &CStr,
+        inputs: &[S],
+        mode: modes::TranslationModes,
+    ) -> Vec<Result<Translation>> {
+        let options = TranslationOptions::default();
+        let mut buffers = Buffers::default();
+        inputs
+            .iter()
+            .map(|input| {
+                self.translate_with_buffers(
+                    table_names,
+                    input.as_ref(),
+                    false,
                     mode,
-                );
+                    &options,
+                    &mut buffers,
+                )
+            })
+            .collect()
+    }
+
+    /// Back-translates the braille in `input` according to the tables given by `table_names`,
+    /// also returning how the positions in the input and output relate to each other.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use louis::{Louis, modes::TranslationModes};
+    /// let louis = Louis::new().unwrap();
+    /// let translation = louis.back_translate("en_US.tbl", ",x \"ws6", TranslationModes::empty()).unwrap();
+    /// assert_eq!(translation.output, "It works!");
+    ///
+    /// // The capital sign and "x" together stand for "It"
+    /// assert_eq!(translation.output_pos[..2], [0, 0]);
+    /// assert_eq!(translation.input_pos[..2], [0, 0]);
+    /// ```
+    pub fn back_translate(
+        &self,
+        table_names: &str,
+        input: &str,
+        mode: modes::TranslationModes,
+    ) -> Result<Translation> {
+        self.back_translate_with_options(table_names, input, mode, &TranslationOptions::default())
+    }
+
+    /// Like `back_translate`, but also takes `TranslationOptions`.
+    ///
+    /// Only the cursor is used, since emphasis and hyphenation can't be applied to braille.
+    ///
+    /// Emphasis in the braille is not reported: liblouis 3.7 accepts a `typeform` array for
+    /// back-translations, but only ever fills it with `'0'`, so there is nothing to recover.
+    pub fn back_translate_with_options(
+        &self,
+        table_names: &str,
+        input: &str,
+        mode: modes::TranslationModes,
+        options: &TranslationOptions,
+    ) -> Result<Translation> {
+        self.translate_positions(&CString::new(table_names)?, input, true, mode, options)
+    }
+
+    /// Finds the positions in `word` before which it may be hyphenated, according to the
+    /// hyphenation dictionary included in `table_names`.
+    ///
+    /// Leading and trailing punctuation is ignored, but the rest of the input has to be a single word
+    /// of less than `MAX_HYPHENATION_LEN` characters. Positions count `louis_sys::widechar`s of `word`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use louis::{Louis, modes::HyphenationMode};
+    /// let louis = Louis::new().unwrap();
+    /// let breaks = louis.hyphenate("en_US.tbl", "hyphenation", HyphenationMode::Print).unwrap();
+    /// assert_eq!(breaks, [2, 6]); // hy-phen-ation
+    /// ```
+    ///
+    /// Tables without a hyphenation dictionary result in an error:
+    ///
+    /// ```
+    /// # use louis::{Louis, Error, modes::HyphenationMode};
+    /// # let louis = Louis::new().unwrap();
+    /// match louis.hyphenate("en-ueb-g1.ctb", "hyphenation", HyphenationMode::Print) {
+    ///     Err(Error::NoHyphenation(_)) => (),
+    ///     other => panic!("unexpected result: {:?}", other),
+    /// }
+    /// ```
+    pub fn hyphenate(
+        &self,
+        table_names: &str,
+        word: &str,
+        mode: modes::HyphenationMode,
+    ) -> Result<Vec<usize>> {
+        self.hyphenate_word(&CString::new(table_names)?, word, mode)
+    }
+
+    pub(crate) fn hyphenate_word(
+        &self,
+        table_names: &CStr,
+        word: &str,
+        mode: modes::HyphenationMode,
+    ) -> Result<Vec<usize>> {
+        let inbuf = LouisString::from_str(word)?;
+        if inbuf.len() >= MAX_HYPHENATION_LEN {
+            return Err(Error::InvalidInput(format!(
+                "cannot hyphenate words of {} or more characters",
+                MAX_HYPHENATION_LEN
+            )));
+        }
+        let braille = mode == modes::HyphenationMode::Braille;
+
+        match self.hyphenate_raw(table_names, inbuf.as_slice(), braille) {
+            Some(hyphens) => Ok(hyphen_positions(&hyphens[..inbuf.len()])),
+            None => {
+                self.check_table(table_names)?;
+                let base_tables = self.base_tables(table_names);
+                if self.has_hyphenation(&base_tables) {
+                    Err(Error::InvalidInput(format!(
+                        "\"{}\" is not a single word",
+                        word
+                    )))
+                } else {
+                    Err(Error::NoHyphenation(
+                        base_tables.to_string_lossy().into_owned(),
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Calls `lou_hyphenate`, returning its array of hyphen marks on success.
+    fn hyphenate_raw(
+        &self,
+        table_names: &CStr,
+        inbuf: &[louis_sys::widechar],
+        braille: bool,
+    ) -> Option<Vec<c_char>> {
+        // In braille mode, liblouis marks the back-translated word, which can be longer than the input
+        let mut hyphens: Vec<c_char> = vec![0; MAX_HYPHENATION_LEN + 1];
+        let success = unsafe {
+            louis_sys::lou_hyphenate(
+                table_names.as_ptr(),
+                inbuf.as_ptr(),
+                inbuf.len() as c_int,
+                hyphens.as_mut_ptr(),
+                braille as c_int,
+            )
+        };
+        if success == 0 {
+            None
+        } else {
+            Some(hyphens)
+        }
+    }
+
+    /// Returns the tables a table list compiled by `compile_with_rules` was built from,
+    /// since its marker file is gone and can't be resolved anymore. Other table lists are returned as they are.
+    fn base_tables(&self, table_names: &CStr) -> CString {
+        self.overlays
+            .borrow()
+            .iter()
+            .find(|&(_, key)| key.as_c_str() == table_names)
+            .and_then(|((base, _), _)| CString::new(base.as_str()).ok())
+            .unwrap_or_else(|| table_names.to_owned())
+    }
+
+    /// liblouis fails the same way when the table has no hyphenation dictionary and when the input
+    /// isn't a word. Tells them apart by looking for a dictionary, i.e. a `.dic` file,
+    /// among the tables and the tables they include.
+    fn has_hyphenation(&self, table_names: &CStr) -> bool {
+        let mut pending = resolve_tables(table_names, None).unwrap_or_default();
+        let mut seen = HashSet::new();
+        while let Some(file) = pending.pop() {
+            if file.extension().is_some_and(|ext| ext == "dic") {
+                return true;
+            }
+            if !seen.insert(file.clone()) {
+                continue;
+            }
+            let (contents, base) = match (
+                std::fs::read(&file),
+                CString::new(file.to_string_lossy().as_bytes()),
+            ) {
+                (Ok(contents), Ok(base)) => (contents, base),
+                _ => continue,
+            };
+            for line in String::from_utf8_lossy(&contents).lines() {
+                let mut words = line.split_whitespace();
+                if words.next() != Some("include") {
+                    continue;
+                }
+                if let Some(Ok(include)) = words.next().map(CString::new) {
+                    pending.extend(resolve_tables(&include, Some(&base)).unwrap_or_default());
+                }
             }
+        }
+        false
+    }
+
+    /// Converts each character of `input` to the braille cell the display tables in `table_names` assign to it,
+    /// without translating. Characters the display tables don't define become empty cells.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use louis::{Louis, braille::BrailleCell};
+    /// let louis = Louis::new().unwrap();
+    /// let cells = louis.char_to_dots("en-us-comp8.ctb", "ab").unwrap();
+    /// assert_eq!(cells, [BrailleCell::DOT_1, BrailleCell::DOT_1 | BrailleCell::DOT_2]);
+    /// ```
+    pub fn char_to_dots(
+        &self,
+        table_names: &str,
+        input: &str,
+    ) -> Result<Vec<braille::BrailleCell>> {
+        let table_names = CString::new(table_names)?;
+        let inbuf = LouisString::from_str(input)?;
+        if inbuf.is_empty() {
+            return self.check_table(&table_names).map(|_| Vec::new());
+        }
+        let mut outbuf: Vec<louis_sys::widechar> = vec![0; inbuf.len()];
+        let success = unsafe {
+            louis_sys::lou_charToDots(
+                table_names.as_ptr(),
+                inbuf.as_ptr(),
+                outbuf.as_mut_ptr(),
+                inbuf.len() as c_int,
+                0,
+            )
         };
+        if success == 0 {
+            return Err(self.translation_error(&table_names));
+        }
+        outbuf
+            .into_iter()
+            .map(|dots| braille::BrailleCell::from_louis_dots(dots).ok_or(Error::TranslationFailed))
+            .collect()
+    }
 
-        unsafe{ outvec.set_len(outlen as usize)};
-        LouisString::new(outvec).unwrap().to_string().unwrap()
+    /// Converts each braille cell in `cells` to the character the display tables in `table_names` assign to it,
+    /// the reverse of `char_to_dots`. Cells the display tables don't define become spaces.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use louis::{Louis, braille::BrailleCell};
+    /// let louis = Louis::new().unwrap();
+    /// let cells = [BrailleCell::DOT_1, BrailleCell::DOT_1 | BrailleCell::DOT_2];
+    /// assert_eq!(louis.dots_to_char("en-us-comp8.ctb", &cells).unwrap(), "ab");
+    /// ```
+    pub fn dots_to_char(
+        &self,
+        table_names: &str,
+        cells: &[braille::BrailleCell],
+    ) -> Result<String> {
+        let table_names = CString::new(table_names)?;
+        if cells.is_empty() {
+            return self.check_table(&table_names).map(|_| String::new());
+        }
+        let mut inbuf: Vec<louis_sys::widechar> =
+            cells.iter().map(|cell| cell.to_louis_dots()).collect();
+        let mut outbuf: Vec<louis_sys::widechar> = vec![0; cells.len()];
+        let success = unsafe {
+            louis_sys::lou_dotsToChar(
+                table_names.as_ptr(),
+                inbuf.as_mut_ptr(),
+                outbuf.as_mut_ptr(),
+                cells.len() as c_int,
+                0,
+            )
+        };
+        if success == 0 {
+            return Err(self.translation_error(&table_names));
+        }
+        from_louis_string(outbuf)
+    }
+
+    /// Calls `lou_translate`, `lou_translatePrehyphenated` or `lou_backTranslate`,
+    /// returning the output along with the position mappings.
+    pub(crate) fn translate_positions(
+        &self,
+        table_names: &CStr,
+        input: &str,
+        backwards: bool,
+        mode: modes::TranslationModes,
+        options: &TranslationOptions,
+    ) -> Result<Translation> {
+        let mut buffers = Buffers::default();
+        self.translate_with_buffers(table_names, input, backwards, mode, options, &mut buffers)
+    }
+
+    /// Like `translate_positions`, but passing `buffers` to liblouis instead of allocating new ones.
+    fn translate_with_buffers(
+        &self,
+        table_names: &CStr,
+        input: &str,
+        backwards: bool,
+        mode: modes::TranslationModes,
+        options: &TranslationOptions,
+        buffers: &mut Buffers,
+    ) -> Result<Translation> {
+        if backwards && (!options.emphasis.is_empty() || options.hyphens.is_some()) {
+            return Err(Error::InvalidInput(
+                "emphasis and hyphenation can not be applied to the input of a back-translation"
+                    .to_owned(),
+            ));
+        }
+        let Buffers {
+            input: inbuf,
+            output: outbuf,
+            output_pos,
+            input_pos,
+        } = buffers;
+        inbuf.clear();
+        inbuf.push_str(input);
+        if let Some(pos) = inbuf.as_slice().iter().position(|&c| c == 0) {
+            return Err(Error::InvalidInput(format!(
+                "NUL character at position {}",
+                pos
+            )));
+        }
+        let inlen = inbuf.len();
+        let typeform = if options.emphasis.is_empty() {
+            None
+        } else {
+            Some(emphasis::typeform_buffer(
+                &options.emphasis,
+                inlen,
+                |class| self.class_typeform(table_names, class),
+            )?)
+        };
+        let cursor = match options.cursor {
+            Some(cursor) if cursor > inlen => {
+                return Err(Error::InvalidInput(format!(
+                    "cursor position {} is out of bounds for input of length {}",
+                    cursor, inlen
+                )))
+            }
+            // liblouis can only place the cursor on a character
+            Some(cursor) if cursor < inlen => Some(cursor as c_int),
+            _ => None,
+        };
+        let input_hyphens = match options.hyphens {
+            Some(ref hyphens) => {
+                let mut marks = vec![b'0' as c_char; inlen + 1];
+                for &pos in hyphens {
+                    if pos >= inlen {
+                        return Err(Error::InvalidInput(format!(
+                            "hyphenation point {} is out of bounds for input of length {}",
+                            pos, inlen
+                        )));
+                    }
+                    marks[pos] = b'1' as c_char;
+                }
+                marks[inlen] = 0;
+                // lou_translatePrehyphenated reports success even if the table is broken
+                self.check_table(table_names)?;
+                Some(marks)
+            }
+            None => None,
+        };
+
+        let (cursor, output_hyphens) = self.with_output_buffer(table_names, inlen, |capacity| {
+            let mut consumed = inlen as c_int;
+            let mut written = capacity as c_int;
+            outbuf.clear();
+            outbuf.resize(capacity, 0);
+            output_pos.clear();
+            output_pos.resize(inlen, 0);
+            input_pos.clear();
+            input_pos.resize(capacity, 0);
+            // liblouis writes to the typeform array for every output character
+            let mut typeform = typeform.clone().map(|mut typeform| {
+                typeform.resize(capacity.max(inlen), 0);
+                typeform
+            });
+            let mut cursor = cursor;
+            let mut output_hyphens = input_hyphens
+                .as_ref()
+                .map(|_| vec![b'0' as c_char; capacity + 1]);
+
+            let typeform_ptr = typeform
+                .as_mut()
+                .map_or(std::ptr::null_mut(), |typeform| typeform.as_mut_ptr());
+            let cursor_ptr = cursor
+                .as_mut()
+                .map_or(std::ptr::null_mut(), |cursor| cursor as *mut _);
+            let success = unsafe {
+                match (input_hyphens.as_ref(), output_hyphens.as_mut()) {
+                    (Some(input_hyphens), Some(output_hyphens)) => {
+                        louis_sys::lou_translatePrehyphenated(
+                            table_names.as_ptr(),
+                            inbuf.as_ptr(),
+                            &mut consumed as *mut _,
+                            outbuf.as_mut_ptr(),
+                            &mut written as *mut _,
+                            typeform_ptr,
+                            std::ptr::null_mut::<c_char>(),
+                            output_pos.as_mut_ptr(),
+                            input_pos.as_mut_ptr(),
+                            cursor_ptr,
+                            // liblouis only reads from this
+                            input_hyphens.as_ptr() as *mut c_char,
+                            output_hyphens.as_mut_ptr(),
+                            mode.bits(),
+                        )
+                    }
+                    _ => {
+                        let translate = if backwards {
+                            louis_sys::lou_backTranslate
+                        } else {
+                            louis_sys::lou_translate
+                        };
+                        translate(
+                            table_names.as_ptr(),
+                            inbuf.as_ptr(),
+                            &mut consumed as *mut _,
+                            outbuf.as_mut_ptr(),
+                            &mut written as *mut _,
+                            typeform_ptr,
+                            std::ptr::null_mut::<c_char>(),
+                            output_pos.as_mut_ptr(),
+                            input_pos.as_mut_ptr(),
+                            cursor_ptr,
+                            mode.bits(),
+                        )
+                    }
+                }
+            };
+            outbuf.truncate(written as usize);
+            output_pos.truncate(consumed as usize);
+            input_pos.truncate(written as usize);
+            if let Some(ref mut output_hyphens) = output_hyphens {
+                output_hyphens.truncate(written as usize);
+            }
+            Attempt {
+                success,
+                consumed,
+                written,
+                output: (cursor, output_hyphens),
+            }
+        })?;
+
+        let braille = if !backwards && mode.contains(modes::TranslationModes::DOTS_IO) {
+            braille::from_output(outbuf)
+        } else {
+            None
+        };
+        let output = widestring::UStr::from_slice(outbuf)
+            .to_string()
+            .map_err(|_| Error::TranslationFailed)?;
+        let output_pos = positions(output_pos, outbuf.len());
+        let cursor = match (options.cursor, cursor) {
+            (Some(_), Some(cursor)) if cursor >= 0 => Some(cursor as usize),
+            // liblouis reports -1 for a cursor on input that produced no output
+            (Some(input_cursor), Some(_)) => Some(output_pos[input_cursor]),
+            (Some(_), None) => Some(input_pos.len()),
+            (None, _) => None,
+        };
+        Ok(Translation {
+            output,
+            braille,
+            input_pos: positions(input_pos, inlen),
+            output_pos,
+            cursor,
+            hyphens: output_hyphens.map(|hyphens| hyphen_positions(&hyphens)),
+        })
+    }
+
+    /// Repeatedly calls `translate` with the capacity of the output buffer to use,
+    /// growing it until liblouis manages to translate all `inlen` input characters.
+    ///
+    /// liblouis does not reliably report running out of space. When a rule's output doesn't fit,
+    /// `translateString` in liblouis/lou_translateString.c jumps to its `failure` label and still
+    /// returns success. It only rewinds to the start of the current word if input remains, so output
+    /// that doesn't fit after the last input character is dropped while `*inlen` claims the whole
+    /// input was consumed. `translatePass` behaves the same way for the later passes.
+    ///
+    /// So a result is only trusted if it leaves at least half of the buffer unused, or if a bigger
+    /// buffer yields the same output length. The initial capacity of `OUTLEN_MULTIPLIER` times the
+    /// input is enough for almost all text, so the second translation is rarely needed.
+    /// Buffers can therefore grow to twice `max_output_len`.
+    fn with_output_buffer<T, F>(
+        &self,
+        table_names: &CStr,
+        inlen: usize,
+        mut translate: F,
+    ) -> Result<T>
+    where
+        F: FnMut(usize) -> Attempt<T>,
+    {
+        let max_capacity = self
+            .max_output_len
+            .saturating_mul(2)
+            .min(c_int::MAX as usize);
+        let mut capacity = inlen.saturating_mul(OUTLEN_MULTIPLIER).min(max_capacity);
+        let mut previous_written = None;
+        loop {
+            let attempt = translate(capacity);
+            if attempt.success == 0 {
+                return Err(self.translation_error(table_names));
+            }
+            let written = attempt.written as usize;
+            let consumed_all = attempt.consumed as usize >= inlen;
+            let roomy = written <= capacity / 2;
+            if consumed_all && (roomy || previous_written == Some(written)) {
+                return if written <= self.max_output_len {
+                    Ok(attempt.output)
+                } else {
+                    Err(Error::OutputTruncated)
+                };
+            } else if capacity >= max_capacity {
+                return Err(Error::OutputTruncated);
+            }
+            previous_written = Some(written);
+            capacity = capacity.saturating_mul(2).min(max_capacity);
+        }
+    }
+
+    /// Makes sure that the tables in `table_names` can be found and compiled.
+    fn check_table(&self, table_names: &CStr) -> Result<()> {
+        let (valid, diagnostics) = self.capture_diagnostics(|| unsafe {
+            louis_sys::lou_checkTable(table_names.as_ptr()) != 0
+        });
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::InvalidTable(TableCompileError {
+                table_names: table_names.to_string_lossy().into_owned(),
+                diagnostics,
+            }))
+        }
+    }
+
+    /// Compiles the tables in `table_names` once more, under a table list of their own,
+    /// and adds `rules` to the result, returning the new table list.
+    ///
+    /// liblouis keeps track of where to add rules to a table only until it compiles the next one,
+    /// so `lou_compileString` has to be called right after compiling the table it modifies.
+    /// Tables built from the same base tables and rules are compiled only once,
+    /// since liblouis doesn't free any of them before `lou_free`.
+    pub(crate) fn compile_with_rules(
+        &self,
+        table_names: &str,
+        rules: &[String],
+    ) -> Result<CString> {
+        if let Some(rule) = rules.iter().find(|rule| rule.len() >= MAX_RULE_LEN) {
+            return Err(Error::InvalidInput(format!(
+                "rule \"{}...\" is longer than {} bytes",
+                rule.chars().take(20).collect::<String>(),
+                MAX_RULE_LEN - 1
+            )));
+        }
+        let rule_strings = rules
+            .iter()
+            .map(|rule| CString::new(rule.as_str()))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let cache_key = (table_names.to_owned(), rules.to_vec());
+        if let Some(key) = self.overlays.borrow().get(&cache_key) {
+            return Ok(key.clone());
+        }
+        self.check_table(&CString::new(table_names)?)?;
+
+        // liblouis caches tables by table list, so an empty table with a unique name
+        // is appended to make it compile a separate copy
+        static OVERLAYS: AtomicUsize = AtomicUsize::new(0);
+        let marker = TempFile(std::env::temp_dir().join(format!(
+            "louis-overlay-{}-{}.tbl",
+            std::process::id(),
+            OVERLAYS.fetch_add(1, Ordering::Relaxed)
+        )));
+        std::fs::write(
+            &marker.0,
+            "# extra rules are added using lou_compileString\n",
+        )?;
+        let key = CString::new(format!("{},{}", table_names, marker.0.display()));
+
+        let (compiled, mut diagnostics) = match key {
+            Ok(ref key) => self.capture_diagnostics(|| unsafe {
+                louis_sys::lou_checkTable(key.as_ptr()) != 0
+                    && rule_strings
+                        .iter()
+                        .all(|rule| louis_sys::lou_compileString(key.as_ptr(), rule.as_ptr()) != 0)
+            }),
+            Err(_) => (false, Vec::new()),
+        };
+        // The compiled table doesn't need the file anymore
+        drop(marker);
+        let key = key?;
+
+        // lou_compileString logs most problems without failing, and uses the rule as the file name
+        for diagnostic in &mut diagnostics {
+            if rules
+                .iter()
+                .any(|rule| diagnostic.file.as_deref() == Some(Path::new(rule)))
+            {
+                diagnostic.file = None;
+                diagnostic.line = None;
+            }
+        }
+        if compiled && diagnostics.iter().all(|d| d.severity != Severity::Error) {
+            self.overlays.borrow_mut().insert(cache_key, key.clone());
+            Ok(key)
+        } else {
+            Err(Error::InvalidTable(TableCompileError {
+                table_names: table_names.to_owned(),
+                diagnostics,
+            }))
+        }
+    }
+
+    /// Runs `f`, collecting the warnings and errors liblouis logs meanwhile.
+    ///
+    /// liblouis doesn't cache tables that fail to compile, so checking a table again
+    /// reports the same problems again.
+    fn capture_diagnostics<T, F: FnOnce() -> T>(&self, f: F) -> (T, Vec<Diagnostic>) {
+        let configured = filter_to_lou_loglevel(log::STATIC_MAX_LEVEL);
+        unsafe { louis_sys::lou_setLogLevel(configured.min(louis_sys::logLevels_LOG_WARN)) };
+        DIAGNOSTICS.with(|diagnostics| *diagnostics.borrow_mut() = Some(Vec::new()));
+        let result = f();
+        let diagnostics = DIAGNOSTICS.with(|diagnostics| diagnostics.borrow_mut().take());
+        unsafe { louis_sys::lou_setLogLevel(configured) };
+        (result, diagnostics.unwrap_or_default())
+    }
+
+    /// Determines why a liblouis translation function returned 0.
+    fn translation_error(&self, table_names: &CStr) -> Error {
+        match self.check_table(table_names) {
+            Err(e) => e,
+            Ok(()) => Error::TranslationFailed,
+        }
     }
 
     fn configure_logging(&self) {
@@ -177,17 +1509,22 @@ impl Louis {
 
 impl Drop for Louis {
     fn drop(&mut self) {
+        self.reset_table_resolver();
+        self.reset_table_index();
         self.reset_logging();
-        unsafe { louis_sys::lou_free() };
+        unsafe {
+            louis_sys::lou_setDataPath(std::ptr::null());
+            louis_sys::lou_free();
+        };
     }
 }
 
 fn lou_loglevel_to_level(level: c_uint) -> log::Level {
     match level {
-        0...louis_sys::logLevels_LOG_ALL => log::Level::Trace,
-        0...louis_sys::logLevels_LOG_DEBUG => log::Level::Debug,
-        0...louis_sys::logLevels_LOG_INFO => log::Level::Info,
-        0...louis_sys::logLevels_LOG_WARN => log::Level::Warn,
+        louis_sys::logLevels_LOG_ALL => log::Level::Trace,
+        _ if level <= louis_sys::logLevels_LOG_DEBUG => log::Level::Debug,
+        _ if level <= louis_sys::logLevels_LOG_INFO => log::Level::Info,
+        _ if level <= louis_sys::logLevels_LOG_WARN => log::Level::Warn,
         _ => log::Level::Error,
     }
 }
@@ -203,8 +1540,169 @@ fn filter_to_lou_loglevel(filter: log::LevelFilter) -> c_uint {
     }
 }
 
+/// Resolves `table` to the single file it refers to, reporting an error otherwise.
+fn resolve_single_table(table: &CStr) -> Option<PathBuf> {
+    let mut files = resolve_tables(table, None)?;
+    if files.len() == 1 {
+        files.pop()
+    } else {
+        report_error(&format!(
+            "Table '{}' resolves to more than one file",
+            table.to_string_lossy()
+        ));
+        None
+    }
+}
+
+/// Resolves the table list `tables` to the files it refers to using the registered resolver,
+/// like liblouis does for the argument of an `include` statement in `base`.
+fn resolve_tables(tables: &CStr, base: Option<&CStr>) -> Option<Vec<PathBuf>> {
+    let base = base.map_or(std::ptr::null(), |base| base.as_ptr());
+    let list_begin = unsafe { louis_sys::_lou_resolveTable(tables.as_ptr(), base) };
+    if list_begin.is_null() {
+        // liblouis has already reported why
+        return None;
+    }
+    let mut files = Vec::new();
+    for offset in 0.. {
+        let ptr = unsafe { *(list_begin.offset(offset)) };
+        if ptr.is_null() {
+            break;
+        }
+        files.push(path_from_c(unsafe { CStr::from_ptr(ptr) }));
+        unsafe { libc::free(ptr as *mut libc::c_void) };
+    }
+    unsafe { libc::free(list_begin as *mut libc::c_void) };
+    Some(files)
+}
+
+/// Converts a path returned by liblouis without assuming that it's valid UTF-8.
+#[cfg(unix)]
+fn path_from_c(path: &CStr) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(path.to_bytes()))
+}
+
+#[cfg(not(unix))]
+fn path_from_c(path: &CStr) -> PathBuf {
+    PathBuf::from(&*path.to_string_lossy())
+}
+
+/// Makes sure that `dir` is an existing directory.
+fn check_dir(dir: &Path) -> Result<()> {
+    if dir.is_dir() {
+        Ok(())
+    } else {
+        Err(Error::InvalidInput(format!(
+            "{} is not a directory",
+            dir.display()
+        )))
+    }
+}
+
+fn path_str(path: &Path) -> Result<&str> {
+    path.to_str()
+        .ok_or_else(|| Error::InvalidInput(format!("{} is not valid UTF-8", path.display())))
+}
+
+/// The buffers `Louis::translate_with_buffers` passes to liblouis,
+/// which `Louis::translate_batch` reuses from one input to the next
+#[derive(Default)]
+struct Buffers {
+    input: widestring::UString<louis_sys::widechar>,
+    output: Vec<louis_sys::widechar>,
+    output_pos: Vec<c_int>,
+    input_pos: Vec<c_int>,
+}
+
+/// A file that is removed when this goes out of scope
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.0) {
+            warn!("Couldn't remove {}: {}", self.0.display(), e);
+        }
+    }
+}
+
+/// The outcome of a single call to a liblouis translation function
+struct Attempt<T> {
+    /// liblouis' return value
+    success: c_int,
+    /// The number of input characters that were translated
+    consumed: c_int,
+    /// The number of characters written to the output buffer
+    written: c_int,
+    output: T,
+}
+
+/// Converts a position mapping from liblouis, which uses -1 for positions without a counterpart.
+/// Those are mapped to the counterpart of the next position that has one, or to `end`.
+fn positions(raw: &[c_int], end: usize) -> Vec<usize> {
+    let mut next = end;
+    let mut positions: Vec<usize> = raw
+        .iter()
+        .rev()
+        .map(|&pos| {
+            if pos >= 0 {
+                next = pos as usize;
+            }
+            next
+        })
+        .collect();
+    positions.reverse();
+    positions
+}
+
+/// Turns an array of liblouis hyphen marks into the positions marked with `'1'`
+fn hyphen_positions(marks: &[c_char]) -> Vec<usize> {
+    marks
+        .iter()
+        .enumerate()
+        .filter(|&(_, &mark)| mark == b'1' as c_char)
+        .map(|(pos, _)| pos)
+        .collect()
+}
+
+fn from_louis_string(buf: Vec<louis_sys::widechar>) -> Result<String> {
+    widestring::UString::from_vec(buf)
+        .to_string()
+        .map_err(|_| Error::TranslationFailed)
+}
+
+thread_local! {
+    /// Collects what liblouis logs while `Louis::capture_diagnostics` is running.
+    /// liblouis calls back on the thread that called into it, which is the one owning `Louis`.
+    static DIAGNOSTICS: RefCell<Option<Vec<Diagnostic>>> = const { RefCell::new(None) };
+}
+
+/// Reports an error that occurred in a callback from liblouis, which can't return a `Result`.
+fn report_error(message: &str) {
+    DIAGNOSTICS.with(|diagnostics| {
+        if let Ok(mut diagnostics) = diagnostics.try_borrow_mut() {
+            if let Some(ref mut diagnostics) = *diagnostics {
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Error,
+                    file: None,
+                    line: None,
+                    message: message.to_owned(),
+                });
+            }
+        }
+    });
+    error!("{}", message);
+}
+
 unsafe extern "C" fn log_callback(level: louis_sys::logLevels, message: *const c_char) {
     let message_str = CStr::from_ptr(message).to_string_lossy();
+    DIAGNOSTICS.with(|diagnostics| {
+        if let Ok(mut diagnostics) = diagnostics.try_borrow_mut() {
+            if let Some(ref mut diagnostics) = *diagnostics {
+                diagnostics.extend(Diagnostic::parse(level, &message_str));
+            }
+        }
+    });
     log!(target: "liblouis", lou_loglevel_to_level(level), "{}", message_str);
 }
 
@@ -0,0 +1,549 @@
+//! Table metadata, as declared by `#+key: value` and `#-key: value` lines at the top of table files.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use super::{Error, Result};
+
+/// A language tag as used in the `locale` metadata of tables, such as `en-US` or `cmn-Hans-CN`.
+///
+/// The tag is kept as it was written, since liblouis compares metadata values literally.
+///
+/// ```
+/// # use louis::metadata::LanguageTag;
+/// let tag: LanguageTag = "cmn-Hans-CN".parse().unwrap();
+/// assert_eq!(tag.language(), "cmn");
+/// assert_eq!(tag.script(), Some("Hans"));
+/// assert_eq!(tag.region(), Some("CN"));
+/// assert!("en US".parse::<LanguageTag>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LanguageTag(String);
+
+impl LanguageTag {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the subtags of the tag. Besides `-`, liblouis' tables also use `_` to separate them.
+    pub fn subtags(&self) -> impl Iterator<Item = &str> {
+        self.0.split(['-', '_'])
+    }
+
+    /// Returns the primary language subtag, such as `en`
+    pub fn language(&self) -> &str {
+        self.subtags().next().unwrap_or_default()
+    }
+
+    /// Returns the script subtag, such as `Hans`
+    pub fn script(&self) -> Option<&str> {
+        self.subtags()
+            .skip(1)
+            .find(|subtag| subtag.len() == 4 && subtag.chars().all(|c| c.is_ascii_alphabetic()))
+    }
+
+    /// Returns the region subtag, such as `US` or `419`
+    pub fn region(&self) -> Option<&str> {
+        self.subtags().skip(1).find(|subtag| {
+            (subtag.len() == 2 && subtag.chars().all(|c| c.is_ascii_alphabetic()))
+                || (subtag.len() == 3 && subtag.chars().all(|c| c.is_ascii_digit()))
+        })
+    }
+}
+
+impl FromStr for LanguageTag {
+    type Err = Error;
+
+    fn from_str(tag: &str) -> Result<Self> {
+        let tag = LanguageTag(tag.to_owned());
+        let valid = tag.subtags().enumerate().all(|(i, subtag)| {
+            (1..=8).contains(&subtag.len())
+                && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+                && (i > 0 || subtag.chars().all(|c| c.is_ascii_alphabetic()))
+        });
+        if valid {
+            Ok(tag)
+        } else {
+            Err(Error::InvalidInput(format!(
+                "\"{}\" is not a language tag",
+                tag.0
+            )))
+        }
+    }
+}
+
+impl fmt::Display for LanguageTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Declares an enum for the values of a metadata field, along with conversions from and to those values
+macro_rules! metadata_enum {
+    ($(#[$attr:meta])* pub enum $name:ident { $($(#[$vattr:meta])* $variant:ident = $value:expr,)* }) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($(#[$vattr])* $variant,)*
+        }
+
+        impl $name {
+            /// Returns the value as it is written in table metadata
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $($name::$variant => $value,)*
+                }
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = Error;
+
+            fn from_str(value: &str) -> Result<Self> {
+                match value {
+                    $($value => Ok($name::$variant),)*
+                    _ => Err(Error::InvalidInput(format!(
+                        "\"{}\" is not a valid {} value",
+                        value,
+                        stringify!($name).to_lowercase()
+                    ))),
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+    };
+}
+
+metadata_enum! {
+    /// How much a table contracts, the `contraction` metadata field
+    pub enum Contraction {
+        /// Uncontracted braille
+        No = "no",
+        /// Braille using only some contractions
+        Partial = "partial",
+        /// Fully contracted braille
+        Full = "full",
+    }
+}
+
+metadata_enum! {
+    /// The number of dots in the cells a table produces, the `dots` metadata field
+    pub enum Dots {
+        Six = "6",
+        Eight = "8",
+    }
+}
+
+metadata_enum! {
+    /// The directions a table can translate in, the `direction` metadata field
+    pub enum Direction {
+        /// The table is only meant for translating to braille
+        Forward = "forward",
+        /// The table is only meant for back-translating from braille
+        Backward = "backward",
+        Both = "both",
+    }
+}
+
+metadata_enum! {
+    /// What kind of text a table is meant for, the `type` metadata field
+    pub enum TableType {
+        Literary = "literary",
+        Computer = "computer",
+    }
+}
+
+/// The metadata of a table, see `Louis::table_info`.
+///
+/// Fields are `None` if the table doesn't declare them, or declares a value that isn't understood.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableInfo {
+    /// The name liblouis knows the table by, which can be passed to `Louis::load_table`.
+    /// For tables found on the table search path, this is the path of the table file.
+    pub table: String,
+    /// The file `table` resolves to
+    pub path: PathBuf,
+    /// The `#-name` of the table, a short human-readable description
+    pub name: Option<String>,
+    /// The `#-display-name` of the table, meant to be shown to users choosing a table
+    pub display_name: Option<String>,
+    pub locale: Option<LanguageTag>,
+    /// The braille grade, such as 1 for uncontracted and 2 for contracted braille.
+    /// Some tables use fractional grades like 1.5.
+    pub grade: Option<f32>,
+    pub contraction: Option<Contraction>,
+    pub dots: Option<Dots>,
+    pub direction: Option<Direction>,
+    pub table_type: Option<TableType>,
+    /// The braille system the table implements, such as `ueb` or `ebae`
+    pub system: Option<String>,
+    /// Whether the table contains hyphenation patterns
+    pub hyphenation: Option<bool>,
+}
+
+impl TableInfo {
+    /// Builds the metadata of `table` from its fields, given by `field`.
+    pub(crate) fn new<F: FnMut(&str) -> Option<String>>(
+        table: String,
+        path: PathBuf,
+        mut field: F,
+    ) -> Self {
+        TableInfo {
+            table,
+            path,
+            name: field("name"),
+            display_name: field("display-name"),
+            locale: parse_field(&mut field, "locale"),
+            grade: parse_field(&mut field, "grade"),
+            contraction: parse_field(&mut field, "contraction"),
+            dots: parse_field(&mut field, "dots"),
+            direction: parse_field(&mut field, "direction"),
+            table_type: parse_field(&mut field, "type"),
+            system: field("system"),
+            hyphenation: field("hyphenation").and_then(|value| match &*value {
+                "yes" => Some(true),
+                "no" => Some(false),
+                _ => None,
+            }),
+        }
+    }
+
+    /// Returns the primary language of the table's locale, in lowercase, such as `de` for `de-CH`.
+    pub fn language(&self) -> Option<String> {
+        self.locale
+            .as_ref()
+            .map(|locale| locale.language().to_ascii_lowercase())
+    }
+
+    /// Orders tables by language, then by locale, then by grade, then by name.
+    /// Tables without a language or grade come last.
+    ///
+    /// ```
+    /// # use louis::{Louis, metadata::TableInfo};
+    /// let louis = Louis::new().unwrap();
+    /// let mut tables = louis.list_tables().unwrap();
+    /// tables.sort_by(TableInfo::cmp_by_language_and_grade);
+    /// ```
+    pub fn cmp_by_language_and_grade(&self, other: &TableInfo) -> Ordering {
+        fn last_if_none<T: PartialOrd>(a: Option<T>, b: Option<T>) -> Ordering {
+            match (a, b) {
+                (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        }
+        last_if_none(self.language(), other.language())
+            .then_with(|| last_if_none(self.locale.as_ref(), other.locale.as_ref()))
+            .then_with(|| last_if_none(self.grade, other.grade))
+            .then_with(|| self.table.cmp(&other.table))
+    }
+}
+
+/// Groups `tables` by their primary language, see `TableInfo::language`,
+/// with each group ordered by `TableInfo::cmp_by_language_and_grade`.
+/// Tables without a locale are grouped under `None`.
+pub fn group_by_language<I: IntoIterator<Item = TableInfo>>(
+    tables: I,
+) -> BTreeMap<Option<String>, Vec<TableInfo>> {
+    let mut groups = BTreeMap::new();
+    for table in tables {
+        groups
+            .entry(table.language())
+            .or_insert_with(Vec::new)
+            .push(table);
+    }
+    for group in groups.values_mut() {
+        group.sort_by(TableInfo::cmp_by_language_and_grade);
+    }
+    groups
+}
+
+fn parse_field<T: FromStr, F: FnMut(&str) -> Option<String>>(
+    field: &mut F,
+    key: &str,
+) -> Option<T> {
+    field(key).and_then(|value| value.parse().ok())
+}
+
+/// A query for tables by their metadata, see `Louis::find_table`.
+///
+/// liblouis scores tables by how well their metadata agrees with the query. Each field the table
+/// agrees on counts in its favour, while fields it disagrees on or doesn't declare count heavily against it,
+/// and only tables with a positive score match. Setting a field twice replaces its value.
+///
+/// ```
+/// # use louis::metadata::{Contraction, TableQuery};
+/// let query = TableQuery::new()
+///     .locale("en-US".parse().unwrap())
+///     .contraction(Contraction::Full)
+///     .grade(2.0);
+/// assert_eq!(query.to_string(), "locale:en-US contraction:full grade:2");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct TableQuery {
+    features: Vec<(String, String)>,
+}
+
+impl TableQuery {
+    pub fn new() -> Self {
+        TableQuery::default()
+    }
+
+    pub fn locale(self, locale: LanguageTag) -> Self {
+        self.feature("locale", locale.as_str())
+    }
+
+    pub fn grade(self, grade: f32) -> Self {
+        self.feature("grade", &grade.to_string())
+    }
+
+    pub fn contraction(self, contraction: Contraction) -> Self {
+        self.feature("contraction", contraction.as_str())
+    }
+
+    pub fn dots(self, dots: Dots) -> Self {
+        self.feature("dots", dots.as_str())
+    }
+
+    pub fn direction(self, direction: Direction) -> Self {
+        self.feature("direction", direction.as_str())
+    }
+
+    pub fn table_type(self, table_type: TableType) -> Self {
+        self.feature("type", table_type.as_str())
+    }
+
+    pub fn system(self, system: &str) -> Self {
+        self.feature("system", system)
+    }
+
+    pub fn hyphenation(self, hyphenation: bool) -> Self {
+        self.feature("hyphenation", if hyphenation { "yes" } else { "no" })
+    }
+
+    /// Queries any other `#+` metadata field.
+    /// Keys and values may only contain ASCII letters, digits, `-`, `.` and `_`.
+    pub fn feature(mut self, key: &str, value: &str) -> Self {
+        match self.features.iter_mut().find(|feature| feature.0 == key) {
+            Some(feature) => feature.1 = value.to_owned(),
+            None => self.features.push((key.to_owned(), value.to_owned())),
+        }
+        self
+    }
+
+    /// Returns the query in liblouis' query syntax, failing if a key or value can't be expressed in it.
+    pub(crate) fn to_query_string(&self) -> Result<String> {
+        let valid = |s: &str| {
+            !s.is_empty()
+                && s.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_')
+        };
+        if let Some((key, value)) = self
+            .features
+            .iter()
+            .find(|(key, value)| !valid(key) || !valid(value))
+        {
+            return Err(Error::InvalidInput(format!(
+                "\"{}:{}\" can't be used in a table query",
+                key, value
+            )));
+        }
+        Ok(self.to_string())
+    }
+}
+
+impl fmt::Display for TableQuery {
+    /// Formats the query in liblouis' query syntax, `key:value key:value`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (key, value)) in self.features.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}:{}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// The level of contraction asked for in `Louis::table_for`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    /// Tables declaring this `contraction`. For tables that only declare a grade,
+    /// grades below 1.5 count as uncontracted and grades of 2 and above as fully contracted.
+    Contraction(Contraction),
+    /// Tables declaring exactly this `grade`. What a grade means differs between braille systems,
+    /// e.g. German grade 1 braille is partially contracted.
+    Grade(f32),
+}
+
+impl From<Contraction> for Level {
+    fn from(contraction: Contraction) -> Self {
+        Level::Contraction(contraction)
+    }
+}
+
+impl From<f32> for Level {
+    fn from(grade: f32) -> Self {
+        Level::Grade(grade)
+    }
+}
+
+/// A table picked by `Louis::table_for`, along with why it was picked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableChoice {
+    pub info: TableInfo,
+    /// How the table meets each criterion, from the most to the least important one
+    pub reasons: Vec<String>,
+}
+
+impl fmt::Display for TableChoice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.info.table, self.reasons.join("; "))
+    }
+}
+
+/// Ranks `tables` as described in `Louis::tables_for`, leaving out those that don't fit at all.
+pub(crate) fn rank(
+    tables: Vec<TableInfo>,
+    locale: &LanguageTag,
+    level: Level,
+    dots: Option<Dots>,
+) -> Vec<TableChoice> {
+    let wanted = normalized_subtags(locale);
+    let mut ranked: Vec<_> = tables
+        .into_iter()
+        .filter_map(|info| {
+            let mut reasons = Vec::new();
+            let locale_rank = rank_locale(&info, locale, &wanted, &mut reasons)?;
+            let level_rank = rank_level(&info, level, &mut reasons)?;
+            let dots_rank = rank_dots(&info, dots, &mut reasons)?;
+            let type_rank = match info.table_type {
+                Some(TableType::Literary) => 0,
+                None => 1,
+                Some(TableType::Computer) => 2,
+            };
+            let direction_rank = match info.direction {
+                Some(Direction::Both) | None => 0,
+                Some(Direction::Forward) => 1,
+                Some(Direction::Backward) => 2,
+            };
+            let key = (
+                locale_rank,
+                level_rank,
+                dots_rank,
+                type_rank,
+                direction_rank,
+            );
+            Some((key, TableChoice { info, reasons }))
+        })
+        .collect();
+    ranked.sort_by(|a, b| {
+        a.0.cmp(&b.0)
+            .then_with(|| a.1.info.table.cmp(&b.1.info.table))
+    });
+    ranked.into_iter().map(|(_, choice)| choice).collect()
+}
+
+/// Compares subtags case-insensitively, since tables write e.g. both `en-US` and `ur_PK`
+fn normalized_subtags(tag: &LanguageTag) -> Vec<String> {
+    tag.subtags()
+        .map(|subtag| subtag.to_ascii_lowercase())
+        .collect()
+}
+
+/// Implements BCP 47 lookup, where `de-CH` falls back to `de`. As a last resort,
+/// tables for more specific locales are used, such as `de-CH` for `de`.
+fn rank_locale(
+    info: &TableInfo,
+    locale: &LanguageTag,
+    wanted: &[String],
+    reasons: &mut Vec<String>,
+) -> Option<usize> {
+    let table_locale = info.locale.as_ref()?;
+    let have = normalized_subtags(table_locale);
+    let common = wanted
+        .iter()
+        .zip(&have)
+        .take_while(|&(wanted, have)| wanted == have)
+        .count();
+    if common == 0 || (common < wanted.len() && common < have.len()) {
+        return None;
+    }
+    if have.len() == wanted.len() {
+        reasons.push(format!("locale {} matches", table_locale));
+        Some(0)
+    } else if have.len() < wanted.len() {
+        reasons.push(format!(
+            "no table for {} fits, fell back to locale {}",
+            locale, table_locale
+        ));
+        Some(wanted.len() - have.len())
+    } else {
+        reasons.push(format!(
+            "no table for {} or a fallback fits, using the more specific locale {}",
+            locale, table_locale
+        ));
+        Some(wanted.len() + have.len())
+    }
+}
+
+fn rank_level(info: &TableInfo, level: Level, reasons: &mut Vec<String>) -> Option<u8> {
+    match level {
+        Level::Grade(grade) if info.grade == Some(grade) => {
+            reasons.push(format!("grade {} matches", grade));
+            Some(0)
+        }
+        Level::Grade(_) => None,
+        Level::Contraction(contraction) => match (info.contraction, info.grade) {
+            (Some(declared), _) if declared == contraction => {
+                reasons.push(format!("contraction {} matches", contraction));
+                Some(0)
+            }
+            (Some(_), _) | (None, None) => None,
+            (None, Some(grade)) => {
+                let implied = if grade < 1.5 {
+                    Contraction::No
+                } else if grade < 2.0 {
+                    Contraction::Partial
+                } else {
+                    Contraction::Full
+                };
+                if implied != contraction {
+                    return None;
+                }
+                reasons.push(format!(
+                    "contraction isn't declared, but grade {} implies {}",
+                    grade, contraction
+                ));
+                Some(1)
+            }
+        },
+    }
+}
+
+fn rank_dots(info: &TableInfo, dots: Option<Dots>, reasons: &mut Vec<String>) -> Option<u8> {
+    match (dots, info.dots) {
+        (None, _) => Some(0),
+        (Some(wanted), Some(declared)) if wanted == declared => {
+            reasons.push(format!("{} dots match", wanted));
+            Some(0)
+        }
+        (Some(_), Some(_)) => None,
+        (Some(wanted), None) => {
+            reasons.push(format!(
+                "the number of dots isn't declared, {} were asked for",
+                wanted
+            ));
+            Some(1)
+        }
+    }
+}
@@ -1,24 +1,78 @@
 use louis_sys::{
-    translationModes_dotsIO, translationModes_noContractions, translationModes_partialTrans,
-    translationModes_ucBrl,
+    translationModes_compbrlAtCursor, translationModes_compbrlLeftCursor, translationModes_dotsIO,
+    translationModes_noContractions, translationModes_noUndefinedDots,
+    translationModes_partialTrans, translationModes_pass1Only, translationModes_ucBrl,
 };
 
+use std::convert::TryFrom;
 use std::os::raw::c_int;
 
-pub type TranslationModes = c_int;
+use super::Error;
+
+bitflags! {
+    /// Flags that change how liblouis translates, mirroring the `translationModes` enum of liblouis.
+    ///
+    /// Flags can be combined using `|`:
+    ///
+    /// ```
+    /// # use louis::modes::TranslationModes;
+    /// let mode = TranslationModes::DOTS_IO | TranslationModes::NO_CONTRACTIONS;
+    /// assert_eq!(format!("{:?}", mode), "NO_CONTRACTIONS | DOTS_IO");
+    /// ```
+    #[derive(Default)]
+    pub struct TranslationModes: c_int {
+        /// Do not perform any contractions
+        const NO_CONTRACTIONS = translationModes_noContractions as c_int;
+        /// Translate the word under the cursor in computer braille
+        const COMPBRL_AT_CURSOR = translationModes_compbrlAtCursor as c_int;
+        /// Output Braille dots instead of the characters given by the display table
+        const DOTS_IO = translationModes_dotsIO as c_int;
+        /// Only perform the first translation pass. This flag is defunct and ignored by liblouis.
+        const PASS1_ONLY = translationModes_pass1Only as c_int;
+        /// Translate the part of the word left of the cursor in computer braille
+        const COMPBRL_LEFT_CURSOR = translationModes_compbrlLeftCursor as c_int;
+        /// When combined with `DOTS_IO`, use Unicode Braille patterns for dots
+        const UC_BRL = translationModes_ucBrl as c_int;
+        /// Do not output anything for characters that are not defined in the table
+        const NO_UNDEFINED_DOTS = translationModes_noUndefinedDots as c_int;
+        /// Treat the input of a back-translation as an incomplete word, see `PARTIAL_TRANS`
+        const PARTIAL_TRANS = translationModes_partialTrans as c_int;
+    }
+}
+
+impl TryFrom<c_int> for TranslationModes {
+    type Error = Error;
+
+    /// Converts a raw liblouis mode into `TranslationModes`, rejecting bits that liblouis doesn't know.
+    fn try_from(bits: c_int) -> Result<Self, Error> {
+        TranslationModes::from_bits(bits).ok_or(Error::InvalidMode(bits))
+    }
+}
 
 /// Output Braille dots using Unicode
-pub const DOTS_UNICODE: TranslationModes =
-    (translationModes_dotsIO | translationModes_ucBrl) as TranslationModes;
+pub const DOTS_UNICODE: TranslationModes = TranslationModes::from_bits_truncate(
+    TranslationModes::DOTS_IO.bits() | TranslationModes::UC_BRL.bits(),
+);
 
 /// Output Braille dots using liblouis' own encoding
-pub const DOTS_LOUIS: TranslationModes = translationModes_dotsIO as TranslationModes;
+pub const DOTS_LOUIS: TranslationModes = TranslationModes::DOTS_IO;
 
 /// Do not perform any contractions
-pub const NO_CONTRACTIONS: TranslationModes = translationModes_noContractions as TranslationModes;
+pub const NO_CONTRACTIONS: TranslationModes = TranslationModes::NO_CONTRACTIONS;
 
 /// This flag specifies that back-translation input should be treated as an incomplete word.
 /// Rules that apply only for complete words or at the end of a word will not take effect.
-/// This is intended to be used when translating input typed on a braille keyboard 
-/// to provide a rough ideato the user of the characters they are typing before the word is complete. 
-pub const PARTIAL_TRANS: TranslationModes = translationModes_partialTrans as TranslationModes;
+/// This is intended to be used when translating input typed on a braille keyboard
+/// to provide a rough ideato the user of the characters they are typing before the word is complete.
+pub const PARTIAL_TRANS: TranslationModes = TranslationModes::PARTIAL_TRANS;
+
+/// Whether the word passed to `Louis::hyphenate` is print or braille,
+/// mirroring the `mode` argument of `lou_hyphenate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HyphenationMode {
+    /// The word is untranslated text
+    Print,
+    /// The word is a translation. liblouis back-translates it, hyphenates the result
+    /// and maps the break positions back onto the braille.
+    Braille,
+}
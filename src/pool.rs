@@ -0,0 +1,663 @@
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use super::modes::{HyphenationMode, TranslationModes};
+use super::process::{self, Request, Response, WORKER_ENV};
+use super::{Error, Result, Translation, TranslationOptions};
+
+/// How many parts `LouisPool::translate_batch` splits a batch into for each helper
+const CHUNKS_PER_WORKER: usize = 4;
+
+/// How long a new helper may take to announce itself
+const HELLO_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to wait before each attempt to replace a helper, in milliseconds
+const RESPAWN_DELAYS: [u64; 3] = [0, 100, 1000];
+
+struct Task {
+    request: Request,
+    /// Overrides the pool's timeout
+    timeout: Option<Duration>,
+    reply: Sender<Result<Response>>,
+}
+
+/// Translates in several helper processes at once, each with its own copy of liblouis.
+///
+/// liblouis can only be used by one thread per process, so a single process can't translate
+/// more than one text at a time. The pool gets around this by starting `workers` helper processes
+/// and handing each request to the next idle one, so that bulk translations scale across cores.
+///
+/// Helpers inherit the environment, so `LOUIS_TABLEPATH` applies to them, but settings made on
+/// a `Louis` in this process, like table directories or resolvers, don't.
+/// If a helper crashes or times out, its request fails with `Error::WorkerCrashed` or `Error::Timeout`
+/// and a new helper takes its place, so that broken tables or pathological inputs can't take down
+/// the calling process. See `LouisPoolBuilder` for timeouts and memory limits.
+/// If no new helper can be started, the request fails with the reason instead, and the pool
+/// carries on with one helper less, see `live_workers`.
+///
+/// The pool is `Sync`, so it can be shared between threads. Dropping it waits for queued requests
+/// to be handled and then stops the helpers.
+///
+/// # Examples
+///
+/// The current executable can serve as the helper, as long as it calls
+/// `process::run_worker_if_requested` before doing anything else:
+///
+/// ```no_run
+/// use louis::{modes::TranslationModes, process, LouisPool};
+///
+/// fn main() {
+///     process::run_worker_if_requested();
+///
+///     let pool = LouisPool::new(4).unwrap();
+///     let chapters = vec!["Chapter one", "Chapter two"];
+///     let braille = pool.translate_all("en-us-g2.ctb", &chapters, TranslationModes::empty());
+///     for chapter in braille {
+///         println!("{}", chapter.unwrap());
+///     }
+/// }
+/// ```
+pub struct LouisPool {
+    sender: Option<Sender<Task>>,
+    dispatchers: Vec<JoinHandle<()>>,
+    /// The number of dispatchers that still have a helper
+    live: Arc<AtomicUsize>,
+}
+
+/// Configures a `LouisPool` before starting its helpers, see `LouisPool::builder`.
+///
+/// A pool with a single worker, a timeout and a memory limit serves as a sandbox
+/// for translating untrusted input or with untrusted tables.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use louis::{modes::TranslationModes, Error, LouisPool};
+/// # use std::time::Duration;
+/// let sandbox = LouisPool::builder()
+///     .workers(1)
+///     .program("/usr/local/bin/lou_worker")
+///     .timeout(Duration::from_secs(10))
+///     .memory_limit(512 << 20)
+///     .build()
+///     .unwrap();
+/// match sandbox.translate_simple("broken.ctb", "text", false, TranslationModes::empty()) {
+///     Ok(braille) => println!("{}", braille),
+///     Err(Error::Timeout) | Err(Error::WorkerCrashed(_)) => println!("skipped"),
+///     Err(e) => panic!("{}", e),
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct LouisPoolBuilder {
+    workers: usize,
+    program: Option<PathBuf>,
+    timeout: Option<Duration>,
+    memory_limit: Option<u64>,
+}
+
+impl LouisPoolBuilder {
+    fn new() -> Self {
+        LouisPoolBuilder {
+            workers: thread::available_parallelism().map_or(1, |n| n.get()),
+            program: None,
+            timeout: None,
+            memory_limit: None,
+        }
+    }
+
+    /// Sets the number of helper processes, which defaults to the number of CPUs.
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Runs `program` as the helper, which must call `process::run_worker`
+    /// or `process::run_worker_if_requested`. Defaults to the current executable.
+    pub fn program<P: Into<PathBuf>>(mut self, program: P) -> Self {
+        self.program = Some(program.into());
+        self
+    }
+
+    /// Limits how long a helper may spend on a request, see `LouisPool::execute_with_timeout`.
+    /// There is no limit by default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Limits the address space of each helper to `bytes`, so that a runaway translation crashes
+    /// its helper instead of exhausting the system's memory. Only supported on Unix.
+    pub fn memory_limit(mut self, bytes: u64) -> Self {
+        self.memory_limit = Some(bytes);
+        self
+    }
+
+    /// Starts the helpers.
+    ///
+    /// Fails with `Error::InvalidInput` if there are no workers, if a memory limit is set
+    /// on a platform that doesn't support it, or if this process is a helper itself.
+    /// The latter happens when the current executable serves as the helper, but doesn't call
+    /// `process::run_worker_if_requested` first, and would otherwise start helpers without end.
+    /// Helpers that don't announce themselves the way `process::run_worker` does are rejected.
+    ///
+    /// ```
+    /// # use louis::{process, Error, LouisPool};
+    /// std::env::set_var(process::WORKER_ENV, "1");
+    /// match LouisPool::new(2) {
+    ///     Err(Error::InvalidInput(_)) => (),
+    ///     other => panic!("unexpected result: {:?}", other.map(|pool| pool.workers())),
+    /// }
+    /// ```
+    pub fn build(self) -> Result<LouisPool> {
+        if env::var_os(WORKER_ENV).is_some() {
+            return Err(Error::InvalidInput(format!(
+                "{} is set, so this is a helper process, which must not start helpers of its own",
+                WORKER_ENV
+            )));
+        }
+        if self.workers == 0 {
+            return Err(Error::InvalidInput(
+                "a pool needs at least one worker".to_owned(),
+            ));
+        }
+        if cfg!(not(unix)) && self.memory_limit.is_some() {
+            return Err(Error::InvalidInput(
+                "memory limits are only supported on Unix".to_owned(),
+            ));
+        }
+        let config = Arc::new(Config {
+            program: match self.program {
+                Some(program) => program,
+                None => env::current_exe()?,
+            },
+            timeout: self.timeout,
+            memory_limit: self.memory_limit,
+        });
+        let (sender, receiver) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let live = Arc::new(AtomicUsize::new(0));
+        let mut dispatchers = Vec::with_capacity(self.workers);
+        for index in 0..self.workers {
+            // If this fails, the helpers started so far are stopped when their dispatchers see the sender go away
+            let worker = Worker::spawn(&config)?;
+            live.fetch_add(1, Ordering::SeqCst);
+            let (config, receiver, live) = (config.clone(), receiver.clone(), live.clone());
+            dispatchers.push(
+                thread::Builder::new()
+                    .name(format!("liblouis-worker-{}", index))
+                    .spawn(move || dispatch(&config, worker, &receiver, &live))?,
+            );
+        }
+        Ok(LouisPool {
+            sender: Some(sender),
+            dispatchers,
+            live,
+        })
+    }
+}
+
+impl LouisPool {
+    /// Starts `workers` helper processes running the current executable, see `process::run_worker_if_requested`.
+    pub fn new(workers: usize) -> Result<Self> {
+        Self::builder().workers(workers).build()
+    }
+
+    /// Starts `workers` helper processes running `program`, which must call `process::run_worker`
+    /// or `process::run_worker_if_requested`.
+    pub fn with_program<P: Into<PathBuf>>(workers: usize, program: P) -> Result<Self> {
+        Self::builder().workers(workers).program(program).build()
+    }
+
+    /// Configures a pool before starting it, e.g. to set a timeout or memory limit.
+    pub fn builder() -> LouisPoolBuilder {
+        LouisPoolBuilder::new()
+    }
+
+    /// The number of helper processes the pool was started with.
+    pub fn workers(&self) -> usize {
+        self.dispatchers.len()
+    }
+
+    /// The number of helper processes that are still available. This falls below `workers`
+    /// for every helper that crashed or timed out and could not be replaced.
+    /// Once it reaches 0, all requests fail.
+    pub fn live_workers(&self) -> usize {
+        self.live.load(Ordering::SeqCst)
+    }
+
+    /// Handles `request` in one of the helper processes, waiting for it to finish.
+    pub fn execute(&self, request: Request) -> Result<Response> {
+        receive(self.submit(request, None))
+    }
+
+    /// Like `execute`, but fails with `Error::Timeout` if the helper spends longer than `timeout` on the request,
+    /// regardless of the pool's timeout. Time spent waiting for an idle helper doesn't count.
+    pub fn execute_with_timeout(&self, request: Request, timeout: Duration) -> Result<Response> {
+        receive(self.submit(request, Some(timeout)))
+    }
+
+    /// Handles all `requests` in parallel and returns their results in the same order.
+    pub fn execute_all<I>(&self, requests: I) -> Vec<Result<Response>>
+    where
+        I: IntoIterator<Item = Request>,
+    {
+        let responses: Vec<_> = requests
+            .into_iter()
+            .map(|request| self.submit(request, None))
+            .collect();
+        responses.into_iter().map(receive).collect()
+    }
+
+    fn submit(&self, request: Request, timeout: Option<Duration>) -> Receiver<Result<Response>> {
+        let (reply, response) = mpsc::channel();
+        let sender = self.sender.as_ref().expect("the pool has been dropped");
+        // If all dispatchers are gone, the reply is dropped and `receive` fails
+        let _ = sender.send(Task {
+            request,
+            timeout,
+            reply,
+        });
+        response
+    }
+
+    /// See `Louis::translate_simple`
+    pub fn translate_simple(
+        &self,
+        table_names: &str,
+        input: &str,
+        backwards: bool,
+        mode: TranslationModes,
+    ) -> Result<String> {
+        self.execute(Request::TranslateSimple {
+            table_names: table_names.to_owned(),
+            input: input.to_owned(),
+            backwards,
+            mode,
+        })?
+        .into_text()
+    }
+
+    /// Like `translate_simple` for each of `inputs`, translating them in parallel.
+    pub fn translate_all<S: AsRef<str>>(
+        &self,
+        table_names: &str,
+        inputs: &[S],
+        mode: TranslationModes,
+    ) -> Vec<Result<String>> {
+        let requests = inputs.iter().map(|input| Request::TranslateSimple {
+            table_names: table_names.to_owned(),
+            input: input.as_ref().to_owned(),
+            backwards: false,
+            mode,
+        });
+        self.execute_all(requests)
+            .into_iter()
+            .map(|response| response?.into_text())
+            .collect()
+    }
+
+    /// Like `Louis::translate_batch`, but spreads the inputs over the helpers in chunks,
+    /// so that each helper looks up the tables once per chunk.
+    ///
+    /// The pool's timeout applies to each chunk. If a chunk times out or its helper crashes,
+    /// all of its inputs fail with that error.
+    pub fn translate_batch<S: AsRef<str>>(
+        &self,
+        table_names: &str,
+        inputs: &[S],
+        mode: TranslationModes,
+    ) -> Vec<Result<Translation>> {
+        // Several chunks per helper even out differences in how long the chunks take
+        let chunk_len = inputs
+            .len()
+            .div_ceil(self.workers() * CHUNKS_PER_WORKER)
+            .max(1);
+        let chunks = inputs.chunks(chunk_len);
+        let chunk_lens: Vec<_> = chunks.clone().map(|chunk| chunk.len()).collect();
+        let requests = chunks.map(|chunk| Request::TranslateBatch {
+            table_names: table_names.to_owned(),
+            inputs: chunk
+                .iter()
+                .map(|input| input.as_ref().to_owned())
+                .collect(),
+            mode,
+        });
+        let mut translations = Vec::with_capacity(inputs.len());
+        for (response, len) in self.execute_all(requests).into_iter().zip(chunk_lens) {
+            match response.and_then(Response::into_translations) {
+                Ok(chunk) => translations.extend(chunk),
+                Err(e) => translations.extend((0..len).map(|_| Err(e.duplicate()))),
+            }
+        }
+        translations
+    }
+
+    /// See `Louis::translate`
+    pub fn translate(
+        &self,
+        table_names: &str,
+        input: &str,
+        mode: TranslationModes,
+    ) -> Result<Translation> {
+        self.translate_with_options(table_names, input, mode, &TranslationOptions::default())
+    }
+
+    /// See `Louis::translate_with_options`
+    pub fn translate_with_options(
+        &self,
+        table_names: &str,
+        input: &str,
+        mode: TranslationModes,
+        options: &TranslationOptions,
+    ) -> Result<Translation> {
+        self.execute(Request::Translate {
+            table_names: table_names.to_owned(),
+            input: input.to_owned(),
+            mode,
+            options: options.clone(),
+        })?
+        .into_translation()
+    }
+
+    /// See `Louis::back_translate`
+    pub fn back_translate(
+        &self,
+        table_names: &str,
+        input: &str,
+        mode: TranslationModes,
+    ) -> Result<Translation> {
+        self.back_translate_with_options(table_names, input, mode, &TranslationOptions::default())
+    }
+
+    /// See `Louis::back_translate_with_options`
+    pub fn back_translate_with_options(
+        &self,
+        table_names: &str,
+        input: &str,
+        mode: TranslationModes,
+        options: &TranslationOptions,
+    ) -> Result<Translation> {
+        self.execute(Request::BackTranslate {
+            table_names: table_names.to_owned(),
+            input: input.to_owned(),
+            mode,
+            options: options.clone(),
+        })?
+        .into_translation()
+    }
+
+    /// See `Louis::hyphenate`
+    pub fn hyphenate(
+        &self,
+        table_names: &str,
+        word: &str,
+        mode: HyphenationMode,
+    ) -> Result<Vec<usize>> {
+        self.execute(Request::Hyphenate {
+            table_names: table_names.to_owned(),
+            word: word.to_owned(),
+            mode,
+        })?
+        .into_hyphens()
+    }
+}
+
+impl Drop for LouisPool {
+    fn drop(&mut self) {
+        // Dispatchers exit once the queue is empty and the sender is gone
+        self.sender.take();
+        for dispatcher in self.dispatchers.drain(..) {
+            let _ = dispatcher.join();
+        }
+    }
+}
+
+fn receive(response: Receiver<Result<Response>>) -> Result<Response> {
+    response.recv().unwrap_or_else(|_| Err(workers_gone()))
+}
+
+fn workers_gone() -> Error {
+    Error::Io(io::Error::new(
+        io::ErrorKind::BrokenPipe,
+        "all helper processes of the pool have failed",
+    ))
+}
+
+/// How to start and supervise helper processes
+struct Config {
+    program: PathBuf,
+    timeout: Option<Duration>,
+    memory_limit: Option<u64>,
+}
+
+/// A helper process, talked to by a thread of its own so that waiting for it can time out
+struct Worker {
+    child: Child,
+    requests: Option<Sender<Vec<u8>>>,
+    responses: Receiver<io::Result<Option<Vec<u8>>>>,
+    io: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+    fn spawn(config: &Config) -> Result<Self> {
+        let mut command = Command::new(&config.program);
+        command
+            .env(WORKER_ENV, "1")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped());
+        if let Some(bytes) = config.memory_limit {
+            limit_memory(&mut command, bytes);
+        }
+        let mut child = command.spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (requests, io_requests) = mpsc::channel();
+        let (io_responses, responses) = mpsc::channel();
+        let io = thread::Builder::new()
+            .name("liblouis-worker-io".to_owned())
+            .spawn(move || talk(stdin, stdout, io_requests, io_responses));
+        let io = match io {
+            Ok(io) => io,
+            Err(e) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(e.into());
+            }
+        };
+        let mut worker = Worker {
+            child,
+            requests: Some(requests),
+            responses,
+            io: Some(io),
+        };
+        worker.greet(&config.program)?;
+        Ok(worker)
+    }
+
+    /// Waits for the helper to announce itself, making sure it speaks our protocol.
+    fn greet(&mut self, program: &Path) -> Result<()> {
+        match self.responses.recv_timeout(HELLO_TIMEOUT) {
+            Ok(Ok(Some(ref hello))) if hello.as_slice() == process::HELLO => Ok(()),
+            Ok(Ok(Some(_))) | Ok(Err(_)) => {
+                let _ = self.child.kill();
+                Err(Error::Io(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "{} is not a helper process of this version of louis",
+                        program.display()
+                    ),
+                )))
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                let _ = self.child.kill();
+                Err(Error::Timeout)
+            }
+            Ok(Ok(None)) | Err(RecvTimeoutError::Disconnected) => Err(self.crashed()),
+        }
+    }
+
+    /// Sends `request` to the helper and waits for the result.
+    /// The outer result fails if the helper is gone and has to be replaced.
+    fn call(&mut self, request: &Request, timeout: Option<Duration>) -> Result<Result<Response>> {
+        let requests = self
+            .requests
+            .as_ref()
+            .expect("requests are open until drop");
+        if requests.send(process::encode(request)).is_err() {
+            return Err(self.crashed());
+        }
+        let response = match timeout {
+            Some(timeout) => self.responses.recv_timeout(timeout),
+            None => self
+                .responses
+                .recv()
+                .map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match response {
+            Ok(Ok(Some(frame))) => process::decode(&frame).map_err(|e| {
+                // The helper is out of step with us, so it can't be trusted with more requests
+                let _ = self.child.kill();
+                e.into()
+            }),
+            Err(RecvTimeoutError::Timeout) => {
+                let _ = self.child.kill();
+                Err(Error::Timeout)
+            }
+            Ok(Ok(None)) | Ok(Err(_)) | Err(RecvTimeoutError::Disconnected) => Err(self.crashed()),
+        }
+    }
+
+    /// Reports how the helper exited, after it stopped talking to us.
+    fn crashed(&mut self) -> Error {
+        // It may still be running, e.g. if it closed its stdout
+        let _ = self.child.kill();
+        match self.child.wait() {
+            Ok(status) => Error::WorkerCrashed(status.to_string()),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        // Closing stdin makes the helper exit, unless it is stuck
+        self.requests.take();
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        if let Some(io) = self.io.take() {
+            let _ = io.join();
+        }
+    }
+}
+
+/// Starts a helper to replace one that is gone, trying a few times in case the problem is temporary.
+fn respawn(config: &Config) -> Result<Worker> {
+    let mut result = Err(workers_gone());
+    for &delay in &RESPAWN_DELAYS {
+        thread::sleep(Duration::from_millis(delay));
+        result = Worker::spawn(config);
+        if result.is_ok() {
+            break;
+        }
+    }
+    result
+}
+
+/// Forwards requests to the helper and its responses back, until either side goes away.
+fn talk(
+    mut stdin: ChildStdin,
+    mut stdout: ChildStdout,
+    requests: Receiver<Vec<u8>>,
+    responses: Sender<io::Result<Option<Vec<u8>>>>,
+) {
+    // The helper speaks first, see `Worker::greet`
+    let hello = process::read_frame(&mut stdout);
+    let failed = !matches!(hello, Ok(Some(_)));
+    if responses.send(hello).is_err() || failed {
+        return;
+    }
+    for request in requests {
+        let response = process::write_frame(&mut stdin, &request)
+            .and_then(|()| process::read_frame(&mut stdout));
+        let failed = !matches!(response, Ok(Some(_)));
+        if responses.send(response).is_err() || failed {
+            break;
+        }
+    }
+}
+
+#[cfg(unix)]
+fn limit_memory(command: &mut Command, bytes: u64) {
+    use std::os::unix::process::CommandExt;
+
+    let limit = libc::rlimit {
+        rlim_cur: bytes as libc::rlim_t,
+        rlim_max: bytes as libc::rlim_t,
+    };
+    // setrlimit is async-signal-safe, so it may be called between fork and exec
+    unsafe {
+        command.pre_exec(move || {
+            if libc::setrlimit(libc::RLIMIT_AS, &limit) == 0 {
+                Ok(())
+            } else {
+                Err(io::Error::last_os_error())
+            }
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn limit_memory(_command: &mut Command, _bytes: u64) {
+    unreachable!("memory limits are rejected by LouisPoolBuilder::build on this platform")
+}
+
+fn dispatch(
+    config: &Config,
+    mut worker: Worker,
+    tasks: &Mutex<Receiver<Task>>,
+    live: &AtomicUsize,
+) {
+    loop {
+        // Only hold the lock while waiting, so other dispatchers can take tasks while this one is busy
+        let task = match tasks.lock() {
+            Ok(tasks) => tasks.recv(),
+            Err(_) => return,
+        };
+        let Task {
+            request,
+            timeout,
+            reply,
+        } = match task {
+            Ok(task) => task,
+            Err(_) => return,
+        };
+        match worker.call(&request, timeout.or(config.timeout)) {
+            Ok(result) => {
+                let _ = reply.send(result);
+            }
+            Err(e) => match respawn(config) {
+                Ok(new_worker) => {
+                    worker = new_worker;
+                    let _ = reply.send(Err(e));
+                }
+                Err(respawn_error) => {
+                    // `e` would claim that the helper was replaced
+                    let remaining = live.fetch_sub(1, Ordering::SeqCst) - 1;
+                    error!(
+                        "could not replace a helper process after \"{}\", {} left: {}",
+                        e, remaining, respawn_error
+                    );
+                    let _ = reply.send(Err(respawn_error));
+                    return;
+                }
+            },
+        }
+    }
+}
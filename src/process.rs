@@ -0,0 +1,765 @@
+//! Running liblouis in helper processes, see `LouisPool`.
+//!
+//! Requests and responses are exchanged over the helpers' stdin and stdout as length-prefixed frames
+//! in a simple binary encoding. Helpers are either the current executable, which has to call
+//! `run_worker_if_requested` at the start of `main`, or a dedicated binary that calls `run_worker`.
+
+use std::env;
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+
+use super::braille::{BrailleCell, BrailleString};
+use super::emphasis::{Emphasis, EmphasisSpan};
+use super::modes::{HyphenationMode, TranslationModes};
+use super::{
+    Diagnostic, Error, Louis, Result, Severity, TableCompileError, Translation, TranslationOptions,
+};
+
+/// The environment variable that tells an executable to act as a helper process, see `run_worker_if_requested`.
+pub const WORKER_ENV: &str = "LOUIS_WORKER";
+
+/// The first frame a helper sends, so that a `LouisPool` can tell it apart from other programs,
+/// and from helpers built against another version of this crate, which may encode things differently
+pub(crate) const HELLO: &[u8] = concat!("louis helper ", env!("CARGO_PKG_VERSION")).as_bytes();
+
+/// Frames larger than this are rejected, so that a corrupted length can't exhaust memory
+const MAX_FRAME_LEN: usize = 1 << 30;
+
+/// A call to one of the translation methods of `Louis`, which can be handled in another process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    /// See `Louis::translate_simple`
+    TranslateSimple {
+        table_names: String,
+        input: String,
+        backwards: bool,
+        mode: TranslationModes,
+    },
+    /// See `Louis::translate_with_options`
+    Translate {
+        table_names: String,
+        input: String,
+        mode: TranslationModes,
+        options: TranslationOptions,
+    },
+    /// See `Louis::back_translate_with_options`
+    BackTranslate {
+        table_names: String,
+        input: String,
+        mode: TranslationModes,
+        options: TranslationOptions,
+    },
+    /// See `Louis::hyphenate`
+    Hyphenate {
+        table_names: String,
+        word: String,
+        mode: HyphenationMode,
+    },
+    /// See `Louis::translate_batch`
+    TranslateBatch {
+        table_names: String,
+        inputs: Vec<String>,
+        mode: TranslationModes,
+    },
+}
+
+/// The result of a successful `Request`.
+#[derive(Debug)]
+pub enum Response {
+    /// The result of `Request::TranslateSimple`
+    Text(String),
+    /// The result of `Request::Translate` and `Request::BackTranslate`
+    Translation(Translation),
+    /// The result of `Request::Hyphenate`
+    Hyphens(Vec<usize>),
+    /// The result of `Request::TranslateBatch`
+    Translations(Vec<Result<Translation>>),
+}
+
+impl Request {
+    /// Handles the request in this process.
+    pub fn execute(&self, louis: &Louis) -> Result<Response> {
+        match self {
+            Request::TranslateSimple {
+                table_names,
+                input,
+                backwards,
+                mode,
+            } => louis
+                .translate_simple(table_names, input, *backwards, *mode)
+                .map(Response::Text),
+            Request::Translate {
+                table_names,
+                input,
+                mode,
+                options,
+            } => louis
+                .translate_with_options(table_names, input, *mode, options)
+                .map(Response::Translation),
+            Request::BackTranslate {
+                table_names,
+                input,
+                mode,
+                options,
+            } => louis
+                .back_translate_with_options(table_names, input, *mode, options)
+                .map(Response::Translation),
+            Request::Hyphenate {
+                table_names,
+                word,
+                mode,
+            } => louis
+                .hyphenate(table_names, word, *mode)
+                .map(Response::Hyphens),
+            Request::TranslateBatch {
+                table_names,
+                inputs,
+                mode,
+            } => Ok(Response::Translations(louis.translate_batch(
+                table_names,
+                inputs,
+                *mode,
+            ))),
+        }
+    }
+}
+
+impl Response {
+    pub(crate) fn into_text(self) -> Result<String> {
+        match self {
+            Response::Text(text) => Ok(text),
+            other => Err(unexpected(&other)),
+        }
+    }
+
+    pub(crate) fn into_translation(self) -> Result<Translation> {
+        match self {
+            Response::Translation(translation) => Ok(translation),
+            other => Err(unexpected(&other)),
+        }
+    }
+
+    pub(crate) fn into_hyphens(self) -> Result<Vec<usize>> {
+        match self {
+            Response::Hyphens(hyphens) => Ok(hyphens),
+            other => Err(unexpected(&other)),
+        }
+    }
+
+    pub(crate) fn into_translations(self) -> Result<Vec<Result<Translation>>> {
+        match self {
+            Response::Translations(translations) => Ok(translations),
+            other => Err(unexpected(&other)),
+        }
+    }
+}
+
+fn unexpected(response: &Response) -> Error {
+    invalid_data(format!("unexpected response {:?}", response)).into()
+}
+
+/// Serves requests from stdin until it is closed, writing the responses to stdout.
+/// Before the first request, it announces itself to the pool.
+///
+/// This is the main loop of a helper process. Nothing else may write to stdout meanwhile.
+pub fn run_worker() -> Result<()> {
+    let louis = Louis::new().ok_or(Error::Unavailable)?;
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let (mut input, mut output) = (stdin.lock(), stdout.lock());
+    write_frame(&mut output, HELLO)?;
+    while let Some(frame) = read_frame(&mut input)? {
+        let request = Request::decode(&mut Decoder::new(&frame))?;
+        let response = request.execute(&louis);
+        write_frame(&mut output, &encode(&response))?;
+    }
+    Ok(())
+}
+
+/// Runs `run_worker` and exits if this process was started as a helper process by a `LouisPool`.
+/// Otherwise, this returns immediately.
+///
+/// Call this at the start of `main` to let a `LouisPool` use the current executable for its helpers.
+pub fn run_worker_if_requested() {
+    if env::var_os(WORKER_ENV).is_none() {
+        return;
+    }
+    let code = match run_worker() {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("louis worker: {}", e);
+            1
+        }
+    };
+    std::process::exit(code);
+}
+
+/// Reads a frame, returning `None` if the stream ended before it.
+pub(crate) fn read_frame<R: Read>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => (),
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(invalid_data(format!("frame of {} bytes is too large", len)));
+    }
+    let mut frame = vec![0; len];
+    reader.read_exact(&mut frame)?;
+    Ok(Some(frame))
+}
+
+pub(crate) fn write_frame<W: Write>(writer: &mut W, frame: &[u8]) -> io::Result<()> {
+    if frame.len() > MAX_FRAME_LEN {
+        return Err(invalid_data(format!(
+            "frame of {} bytes is too large",
+            frame.len()
+        )));
+    }
+    writer.write_all(&(frame.len() as u32).to_le_bytes())?;
+    writer.write_all(frame)?;
+    writer.flush()
+}
+
+pub(crate) fn encode<T: Wire>(value: &T) -> Vec<u8> {
+    let mut encoder = Encoder(Vec::new());
+    value.encode(&mut encoder);
+    encoder.0
+}
+
+pub(crate) fn decode<T: Wire>(frame: &[u8]) -> io::Result<T> {
+    T::decode(&mut Decoder::new(frame))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub(crate) struct Encoder(Vec<u8>);
+
+impl Encoder {
+    fn tag(&mut self, tag: u8) {
+        self.0.push(tag);
+    }
+
+    fn len(&mut self, len: usize) {
+        (len as u64).encode(self);
+    }
+}
+
+pub(crate) struct Decoder<'a>(&'a [u8]);
+
+impl<'a> Decoder<'a> {
+    fn new(frame: &'a [u8]) -> Self {
+        Decoder(frame)
+    }
+
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        if n > self.0.len() {
+            return Err(invalid_data("truncated frame".to_owned()));
+        }
+        let (taken, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn tag(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn len(&mut self) -> io::Result<usize> {
+        let len = u64::decode(self)? as usize;
+        // Every element takes at least a byte, which also bounds preallocation
+        if len > self.0.len() {
+            return Err(invalid_data("truncated frame".to_owned()));
+        }
+        Ok(len)
+    }
+}
+
+fn unknown_tag<T>(tag: u8, what: &str) -> io::Result<T> {
+    Err(invalid_data(format!("unknown {} tag {}", what, tag)))
+}
+
+/// A type that can be sent to or received from a helper process
+pub(crate) trait Wire: Sized {
+    fn encode(&self, encoder: &mut Encoder);
+    fn decode(decoder: &mut Decoder) -> io::Result<Self>;
+}
+
+impl Wire for u8 {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.0.push(*self);
+    }
+
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        decoder.tag()
+    }
+}
+
+impl Wire for bool {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.tag(*self as u8);
+    }
+
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        match decoder.tag()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            tag => unknown_tag(tag, "bool"),
+        }
+    }
+}
+
+impl Wire for u64 {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.0.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(decoder.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
+
+impl Wire for i32 {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.0.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(decoder.take(4)?);
+        Ok(i32::from_le_bytes(bytes))
+    }
+}
+
+impl Wire for usize {
+    fn encode(&self, encoder: &mut Encoder) {
+        (*self as u64).encode(encoder);
+    }
+
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(u64::decode(decoder)? as usize)
+    }
+}
+
+impl Wire for String {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.len(self.len());
+        encoder.0.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        let len = decoder.len()?;
+        String::from_utf8(decoder.take(len)?.to_vec()).map_err(|e| invalid_data(e.to_string()))
+    }
+}
+
+impl<T: Wire> Wire for Option<T> {
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            None => encoder.tag(0),
+            Some(value) => {
+                encoder.tag(1);
+                value.encode(encoder);
+            }
+        }
+    }
+
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        match decoder.tag()? {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode(decoder)?)),
+            tag => unknown_tag(tag, "option"),
+        }
+    }
+}
+
+impl<T: Wire> Wire for Vec<T> {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.len(self.len());
+        for item in self {
+            item.encode(encoder);
+        }
+    }
+
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        let len = decoder.len()?;
+        (0..len).map(|_| T::decode(decoder)).collect()
+    }
+}
+
+impl Wire for Range<usize> {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.start.encode(encoder);
+        self.end.encode(encoder);
+    }
+
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(usize::decode(decoder)?..usize::decode(decoder)?)
+    }
+}
+
+impl Wire for TranslationModes {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.bits().encode(encoder);
+    }
+
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        let bits = i32::decode(decoder)?;
+        TranslationModes::from_bits(bits)
+            .ok_or_else(|| invalid_data(format!("invalid translation mode {:#x}", bits)))
+    }
+}
+
+impl Wire for HyphenationMode {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.tag(match self {
+            HyphenationMode::Print => 0,
+            HyphenationMode::Braille => 1,
+        });
+    }
+
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        match decoder.tag()? {
+            0 => Ok(HyphenationMode::Print),
+            1 => Ok(HyphenationMode::Braille),
+            tag => unknown_tag(tag, "hyphenation mode"),
+        }
+    }
+}
+
+impl Wire for Emphasis {
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            Emphasis::Italic => encoder.tag(0),
+            Emphasis::Underline => encoder.tag(1),
+            Emphasis::Bold => encoder.tag(2),
+            Emphasis::Emph(n) => {
+                encoder.tag(3);
+                n.encode(encoder);
+            }
+            Emphasis::ComputerBraille => encoder.tag(4),
+            Emphasis::NoTranslate => encoder.tag(5),
+            Emphasis::NoContract => encoder.tag(6),
+            Emphasis::Class(class) => {
+                encoder.tag(7);
+                class.encode(encoder);
+            }
+        }
+    }
+
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(match decoder.tag()? {
+            0 => Emphasis::Italic,
+            1 => Emphasis::Underline,
+            2 => Emphasis::Bold,
+            3 => Emphasis::Emph(u8::decode(decoder)?),
+            4 => Emphasis::ComputerBraille,
+            5 => Emphasis::NoTranslate,
+            6 => Emphasis::NoContract,
+            7 => Emphasis::Class(String::decode(decoder)?),
+            tag => return unknown_tag(tag, "emphasis"),
+        })
+    }
+}
+
+impl Wire for EmphasisSpan {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.range.encode(encoder);
+        self.emphasis.encode(encoder);
+    }
+
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(EmphasisSpan::new(
+            Range::decode(decoder)?,
+            Emphasis::decode(decoder)?,
+        ))
+    }
+}
+
+impl Wire for BrailleString {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.len(self.len());
+        encoder.0.extend(self.iter().map(|cell| cell.bits()));
+    }
+
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        let len = decoder.len()?;
+        Ok(decoder
+            .take(len)?
+            .iter()
+            .map(|&bits| BrailleCell::from_bits_truncate(bits))
+            .collect())
+    }
+}
+
+impl Wire for TranslationOptions {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.emphasis.encode(encoder);
+        self.cursor.encode(encoder);
+        self.hyphens.encode(encoder);
+    }
+
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(TranslationOptions {
+            emphasis: Wire::decode(decoder)?,
+            cursor: Wire::decode(decoder)?,
+            hyphens: Wire::decode(decoder)?,
+        })
+    }
+}
+
+impl Wire for Translation {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.output.encode(encoder);
+        self.braille.encode(encoder);
+        self.output_pos.encode(encoder);
+        self.input_pos.encode(encoder);
+        self.cursor.encode(encoder);
+        self.hyphens.encode(encoder);
+    }
+
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Translation {
+            output: Wire::decode(decoder)?,
+            braille: Wire::decode(decoder)?,
+            output_pos: Wire::decode(decoder)?,
+            input_pos: Wire::decode(decoder)?,
+            cursor: Wire::decode(decoder)?,
+            hyphens: Wire::decode(decoder)?,
+        })
+    }
+}
+
+impl Wire for Diagnostic {
+    fn encode(&self, encoder: &mut Encoder) {
+        (self.severity == Severity::Error).encode(encoder);
+        self.file
+            .as_ref()
+            .map(|file| file.to_string_lossy().into_owned())
+            .encode(encoder);
+        self.line.encode(encoder);
+        self.message.encode(encoder);
+    }
+
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Diagnostic {
+            severity: if bool::decode(decoder)? {
+                Severity::Error
+            } else {
+                Severity::Warning
+            },
+            file: Option::<String>::decode(decoder)?.map(PathBuf::from),
+            line: Wire::decode(decoder)?,
+            message: Wire::decode(decoder)?,
+        })
+    }
+}
+
+impl Wire for Error {
+    /// Errors that only make sense within a process are sent as `Error::Io` with their message
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            Error::InvalidTable(e) => {
+                encoder.tag(0);
+                e.table_names.encode(encoder);
+                e.diagnostics.encode(encoder);
+            }
+            Error::TranslationFailed => encoder.tag(1),
+            Error::InvalidInput(reason) => {
+                encoder.tag(2);
+                reason.encode(encoder);
+            }
+            Error::OutputTruncated => encoder.tag(3),
+            Error::InvalidMode(bits) => {
+                encoder.tag(4);
+                bits.encode(encoder);
+            }
+            Error::NoHyphenation(table_names) => {
+                encoder.tag(5);
+                table_names.encode(encoder);
+            }
+            Error::Unavailable => encoder.tag(6),
+            other => {
+                encoder.tag(7);
+                other.to_string().encode(encoder);
+            }
+        }
+    }
+
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(match decoder.tag()? {
+            0 => Error::InvalidTable(TableCompileError {
+                table_names: Wire::decode(decoder)?,
+                diagnostics: Wire::decode(decoder)?,
+            }),
+            1 => Error::TranslationFailed,
+            2 => Error::InvalidInput(Wire::decode(decoder)?),
+            3 => Error::OutputTruncated,
+            4 => Error::InvalidMode(Wire::decode(decoder)?),
+            5 => Error::NoHyphenation(Wire::decode(decoder)?),
+            6 => Error::Unavailable,
+            7 => Error::Io(io::Error::other(String::decode(decoder)?)),
+            tag => return unknown_tag(tag, "error"),
+        })
+    }
+}
+
+impl<T: Wire> Wire for Result<T> {
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            Ok(value) => {
+                encoder.tag(0);
+                value.encode(encoder);
+            }
+            Err(e) => {
+                encoder.tag(1);
+                e.encode(encoder);
+            }
+        }
+    }
+
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        match decoder.tag()? {
+            0 => Ok(Ok(T::decode(decoder)?)),
+            1 => Ok(Err(Error::decode(decoder)?)),
+            tag => unknown_tag(tag, "result"),
+        }
+    }
+}
+
+impl Wire for Request {
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            Request::TranslateSimple {
+                table_names,
+                input,
+                backwards,
+                mode,
+            } => {
+                encoder.tag(0);
+                table_names.encode(encoder);
+                input.encode(encoder);
+                backwards.encode(encoder);
+                mode.encode(encoder);
+            }
+            Request::Translate {
+                table_names,
+                input,
+                mode,
+                options,
+            } => {
+                encoder.tag(1);
+                table_names.encode(encoder);
+                input.encode(encoder);
+                mode.encode(encoder);
+                options.encode(encoder);
+            }
+            Request::BackTranslate {
+                table_names,
+                input,
+                mode,
+                options,
+            } => {
+                encoder.tag(2);
+                table_names.encode(encoder);
+                input.encode(encoder);
+                mode.encode(encoder);
+                options.encode(encoder);
+            }
+            Request::Hyphenate {
+                table_names,
+                word,
+                mode,
+            } => {
+                encoder.tag(3);
+                table_names.encode(encoder);
+                word.encode(encoder);
+                mode.encode(encoder);
+            }
+            Request::TranslateBatch {
+                table_names,
+                inputs,
+                mode,
+            } => {
+                encoder.tag(4);
+                table_names.encode(encoder);
+                inputs.encode(encoder);
+                mode.encode(encoder);
+            }
+        }
+    }
+
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(match decoder.tag()? {
+            0 => Request::TranslateSimple {
+                table_names: Wire::decode(decoder)?,
+                input: Wire::decode(decoder)?,
+                backwards: Wire::decode(decoder)?,
+                mode: Wire::decode(decoder)?,
+            },
+            1 => Request::Translate {
+                table_names: Wire::decode(decoder)?,
+                input: Wire::decode(decoder)?,
+                mode: Wire::decode(decoder)?,
+                options: Wire::decode(decoder)?,
+            },
+            2 => Request::BackTranslate {
+                table_names: Wire::decode(decoder)?,
+                input: Wire::decode(decoder)?,
+                mode: Wire::decode(decoder)?,
+                options: Wire::decode(decoder)?,
+            },
+            3 => Request::Hyphenate {
+                table_names: Wire::decode(decoder)?,
+                word: Wire::decode(decoder)?,
+                mode: Wire::decode(decoder)?,
+            },
+            4 => Request::TranslateBatch {
+                table_names: Wire::decode(decoder)?,
+                inputs: Wire::decode(decoder)?,
+                mode: Wire::decode(decoder)?,
+            },
+            tag => return unknown_tag(tag, "request"),
+        })
+    }
+}
+
+impl Wire for Response {
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            Response::Text(text) => {
+                encoder.tag(0);
+                text.encode(encoder);
+            }
+            Response::Translation(translation) => {
+                encoder.tag(1);
+                translation.encode(encoder);
+            }
+            Response::Hyphens(hyphens) => {
+                encoder.tag(2);
+                hyphens.encode(encoder);
+            }
+            Response::Translations(translations) => {
+                encoder.tag(3);
+                translations.encode(encoder);
+            }
+        }
+    }
+
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(match decoder.tag()? {
+            0 => Response::Text(Wire::decode(decoder)?),
+            1 => Response::Translation(Wire::decode(decoder)?),
+            2 => Response::Hyphens(Wire::decode(decoder)?),
+            3 => Response::Translations(Wire::decode(decoder)?),
+            tag => return unknown_tag(tag, "response"),
+        })
+    }
+}
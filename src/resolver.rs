@@ -0,0 +1,245 @@
+use std::any::Any;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::ptr;
+use std::sync::{Mutex, MutexGuard};
+
+use super::{report_error, Error, Result};
+
+/// What a table name resolves to, see `TableResolver`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TableSource {
+    /// A table file on disk
+    File(PathBuf),
+    /// A table that isn't stored on disk, e.g. one loaded from a database or embedded in the binary.
+    /// liblouis can only compile files, so the contents are written to a temporary file.
+    /// `name` is passed to the resolver as the `base` of the table's `include` statements.
+    Contents { name: String, contents: Vec<u8> },
+}
+
+/// Decides which tables the table names passed to liblouis refer to, see `Louis::set_table_resolver`.
+///
+/// This is implemented for closures taking the same arguments as `resolve`.
+pub trait TableResolver: Send {
+    /// Resolves `tables`, which is either a comma-separated table list passed to one of the methods of `Louis`,
+    /// or the argument of an `include` statement in the table `base`.
+    ///
+    /// Returning `None` lets liblouis' default resolver handle `tables` instead.
+    /// `include` statements have to resolve to a single table.
+    fn resolve(&mut self, tables: &str, base: Option<&str>) -> Option<Vec<TableSource>>;
+}
+
+impl<F> TableResolver for F
+where
+    F: FnMut(&str, Option<&str>) -> Option<Vec<TableSource>> + Send,
+{
+    fn resolve(&mut self, tables: &str, base: Option<&str>) -> Option<Vec<TableSource>> {
+        self(tables, base)
+    }
+}
+
+/// The files of the last resolved tables, kept alive until liblouis has copied them
+struct Files {
+    files: Vec<CString>,
+    /// Null-terminated pointers into `files`, as liblouis expects them
+    pointers: Vec<*mut c_char>,
+}
+
+// The raw pointers only point into `files`, which moves along with them
+unsafe impl Send for Files {}
+
+impl Files {
+    const fn new() -> Self {
+        Files {
+            files: Vec::new(),
+            pointers: Vec::new(),
+        }
+    }
+
+    /// Keeps `files` alive and returns them the way liblouis expects them from a resolver.
+    fn hand_out(&mut self, files: Vec<CString>) -> *mut *mut c_char {
+        self.pointers = files
+            .iter()
+            .map(|file| file.as_ptr() as *mut c_char)
+            .chain(Some(ptr::null_mut()))
+            .collect();
+        self.files = files;
+        self.pointers.as_mut_ptr()
+    }
+}
+
+struct State {
+    resolver: Box<dyn TableResolver>,
+    files: Files,
+    /// Where the contents of `TableSource::Contents` are written to
+    contents_dir: PathBuf,
+    /// The names of the tables written to `contents_dir`, by file
+    content_names: HashMap<PathBuf, String>,
+}
+
+/// liblouis' resolver callback doesn't take any user data, so the resolver has to be global.
+/// There is only ever one `Louis` anyway.
+static STATE: Mutex<Option<State>> = Mutex::new(None);
+
+/// The files resolved while no resolver is registered.
+/// liblouis only frees the results of its own resolver, so they are kept here instead.
+static DEFAULT_FILES: Mutex<Files> = Mutex::new(Files::new());
+
+fn lock() -> MutexGuard<'static, Option<State>> {
+    // A panic while resolving is caught without leaving the state inconsistent
+    STATE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+pub(crate) fn register(resolver: Box<dyn TableResolver>) {
+    let state = State {
+        resolver,
+        files: Files::new(),
+        contents_dir: std::env::temp_dir().join(format!("louis-tables-{}", std::process::id())),
+        content_names: HashMap::new(),
+    };
+    if let Some(old) = lock().replace(state) {
+        old.remove_contents();
+    }
+    unsafe { louis_sys::lou_registerTableResolver(Some(resolve_table)) };
+}
+
+pub(crate) fn unregister() {
+    unsafe { louis_sys::lou_registerTableResolver(Some(louis_sys::_lou_defaultTableResolver)) };
+    if let Some(old) = lock().take() {
+        old.remove_contents();
+    }
+}
+
+impl State {
+    fn resolve(&mut self, tables: *const c_char, base: *const c_char) -> Result<*mut *mut c_char> {
+        let tables_str = unsafe { CStr::from_ptr(tables) }.to_string_lossy();
+        let base_path = if base.is_null() {
+            None
+        } else {
+            Some(PathBuf::from(
+                &*unsafe { CStr::from_ptr(base) }.to_string_lossy(),
+            ))
+        };
+        let base_name = base_path.map(|path| match self.content_names.get(&path) {
+            Some(name) => name.clone(),
+            None => path.to_string_lossy().into_owned(),
+        });
+
+        let files = match self.resolver.resolve(&tables_str, base_name.as_deref()) {
+            Some(sources) => sources
+                .into_iter()
+                .map(|source| self.materialize(source))
+                .collect::<Result<Vec<_>>>()?,
+            None => match default_resolve(tables, base) {
+                Some(files) => files,
+                // The default resolver has already logged why
+                None => return Ok(ptr::null_mut()),
+            },
+        };
+        if files.is_empty() {
+            return Err(Error::InvalidInput("no tables were returned".to_owned()));
+        }
+        Ok(self.files.hand_out(files))
+    }
+
+    /// Returns the path of the file liblouis should read for `source`.
+    fn materialize(&mut self, source: TableSource) -> Result<CString> {
+        let path = match source {
+            TableSource::File(path) => path,
+            TableSource::Contents { name, contents } => {
+                // Tables are resolved every time they are compiled, so the same table
+                // is written only once, to a file named after its name and contents
+                let mut hasher = DefaultHasher::new();
+                (&name, &contents).hash(&mut hasher);
+                let path = self
+                    .contents_dir
+                    .join(format!("{:016x}.tbl", hasher.finish()));
+                if !self.content_names.contains_key(&path) {
+                    fs::create_dir_all(&self.contents_dir)?;
+                    fs::write(&path, contents)?;
+                    self.content_names.insert(path.clone(), name);
+                }
+                path
+            }
+        };
+        let path = path
+            .to_str()
+            .ok_or_else(|| Error::InvalidInput(format!("{} is not valid UTF-8", path.display())))?;
+        Ok(CString::new(path)?)
+    }
+
+    fn remove_contents(&self) {
+        if !self.content_names.is_empty() {
+            let _ = fs::remove_dir_all(&self.contents_dir);
+        }
+    }
+}
+
+/// Calls liblouis' default resolver, taking ownership of the files it returns.
+fn default_resolve(tables: *const c_char, base: *const c_char) -> Option<Vec<CString>> {
+    unsafe {
+        let list = louis_sys::_lou_defaultTableResolver(tables, base);
+        if list.is_null() {
+            return None;
+        }
+        let mut files = Vec::new();
+        for offset in 0.. {
+            let file = *list.offset(offset);
+            if file.is_null() {
+                break;
+            }
+            files.push(CStr::from_ptr(file).to_owned());
+            libc::free(file as *mut libc::c_void);
+        }
+        libc::free(list as *mut libc::c_void);
+        Some(files)
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
+
+unsafe extern "C" fn resolve_table(tables: *const c_char, base: *const c_char) -> *mut *mut c_char {
+    // Unwinding into liblouis is undefined behaviour, so panics are turned into resolution failures
+    let result = panic::catch_unwind(AssertUnwindSafe(|| match *lock() {
+        Some(ref mut state) => state.resolve(tables, base),
+        None => Ok(match default_resolve(tables, base) {
+            Some(files) => DEFAULT_FILES
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .hand_out(files),
+            None => ptr::null_mut(),
+        }),
+    }));
+    let tables = CStr::from_ptr(tables).to_string_lossy();
+    match result {
+        Ok(Ok(files)) => files,
+        Ok(Err(e)) => {
+            report_error(&format!("could not resolve table '{}': {}", tables, e));
+            ptr::null_mut()
+        }
+        Err(payload) => {
+            report_error(&format!(
+                "table resolver panicked while resolving '{}': {}",
+                tables,
+                panic_message(&*payload)
+            ));
+            ptr::null_mut()
+        }
+    }
+}
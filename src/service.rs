@@ -0,0 +1,559 @@
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::Waker;
+#[cfg(feature = "tokio")]
+use std::task::{Context, Poll};
+use std::thread::{self, JoinHandle, ThreadId};
+
+use super::modes::{HyphenationMode, TranslationModes};
+use super::{Error, Louis, Result, Translation, TranslationOptions};
+
+pub(crate) type Job = Box<dyn FnOnce(&mut Louis) + Send>;
+
+enum Message {
+    Job(Job, Arc<Slot>),
+    /// Wakes up the thread after `stopping` has been set
+    Shutdown,
+}
+
+/// The outcome of trying to queue a job
+// Only asynchronous requests keep the slot or the job
+#[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+pub(crate) enum Submitted {
+    Queued(Arc<Slot>),
+    /// There was no room in the queue
+    Full(Job),
+}
+
+/// A handle to a `Louis` running on a dedicated thread, which can be shared between threads.
+///
+/// Requests are queued and handled one at a time, in order. Once the queue is full,
+/// further requests block until there is room again, or fail with `Error::Unavailable`
+/// when made using `try_run`. Cloning the service is cheap and
+/// yields another handle to the same thread.
+///
+/// The thread exits and drops the `Louis`, freeing all of liblouis' memory, when `shutdown` is called
+/// or the last handle is dropped. Requests queued before that are still handled.
+///
+/// # Examples
+///
+/// ```
+/// # use louis::{Louis, LouisService, modes::TranslationModes};
+/// # use std::thread;
+/// let service = LouisService::new(Louis::new().unwrap(), 16).unwrap();
+/// let workers: Vec<_> = (0..4)
+///     .map(|_| {
+///         let service = service.clone();
+///         thread::spawn(move || {
+///             service
+///                 .translate_simple("en_US.tbl", "the hand", false, TranslationModes::empty())
+///                 .unwrap()
+///         })
+///     })
+///     .collect();
+/// for worker in workers {
+///     assert_eq!(worker.join().unwrap(), "! h&");
+/// }
+/// ```
+#[derive(Clone)]
+pub struct LouisService {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    sender: Sender<Message>,
+    slots: Arc<Slots>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+    /// The id of the service's thread, to catch jobs that make requests to their own service
+    thread_id: ThreadId,
+    /// Set by `shutdown`, after which no more requests are accepted
+    stopping: Arc<AtomicBool>,
+}
+
+impl LouisService {
+    /// Moves `louis` to a new thread, queueing at most `queue_len` requests for it.
+    /// With a `queue_len` of 0, each request waits until the thread is ready to handle it.
+    pub fn new(louis: Louis, queue_len: usize) -> Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let stopping = Arc::new(AtomicBool::new(false));
+        let serve_stopping = stopping.clone();
+        let thread = thread::Builder::new()
+            .name("liblouis".to_owned())
+            .spawn(move || serve(louis, receiver, &serve_stopping))?;
+        Ok(LouisService {
+            inner: Arc::new(Inner {
+                sender,
+                // One for the request being handled, and `queue_len` for those waiting for it
+                slots: Arc::new(Slots::new(queue_len + 1)),
+                thread_id: thread.thread().id(),
+                thread: Mutex::new(Some(thread)),
+                stopping,
+            }),
+        })
+    }
+
+    /// Runs `f` on the service's thread and returns its result, for anything the other methods don't cover.
+    ///
+    /// Fails with `Error::ServiceStopped` if the service has been shut down.
+    /// If `f` panics, the panic is resumed on the calling thread, while the service keeps running.
+    /// Jobs can't make requests to their own service, since they would wait for themselves:
+    /// those fail with `Error::Unavailable`.
+    ///
+    /// ```
+    /// # use louis::{Error, Louis, LouisService};
+    /// # use std::panic::{self, AssertUnwindSafe};
+    /// let service = LouisService::new(Louis::new().unwrap(), 16).unwrap();
+    /// let version = service.run(|louis| louis.version()).unwrap().unwrap();
+    /// assert!(version.major >= 3);
+    ///
+    /// let panicked = panic::catch_unwind(AssertUnwindSafe(|| service.run(|_| panic!("oops"))));
+    /// assert_eq!(panicked.unwrap_err().downcast_ref::<&str>(), Some(&"oops"));
+    ///
+    /// let handle = service.clone();
+    /// match service.run(move |_| handle.run(|_| ())).unwrap() {
+    ///     Err(Error::Unavailable) => (),
+    ///     other => panic!("unexpected result: {:?}", other),
+    /// }
+    /// assert_eq!(service.run(|_| 1).unwrap(), 1);
+    /// ```
+    pub fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Louis) -> T + Send + 'static,
+    {
+        self.call(f, true)
+    }
+
+    /// Like `run`, but fails with `Error::Unavailable` instead of waiting if the queue is full.
+    ///
+    /// ```
+    /// # use louis::{Error, Louis, LouisService};
+    /// # use std::sync::mpsc;
+    /// // Without a queue, requests only get through while the thread is idle
+    /// let service = LouisService::new(Louis::new().unwrap(), 0).unwrap();
+    ///
+    /// // Keep the service's thread busy until `proceed` is sent
+    /// let (started, start) = mpsc::channel();
+    /// let (proceed, wait) = mpsc::channel::<()>();
+    /// let handle = service.clone();
+    /// let busy = std::thread::spawn(move || {
+    ///     handle.run(move |_| {
+    ///         started.send(()).unwrap();
+    ///         wait.recv().unwrap()
+    ///     })
+    /// });
+    /// start.recv().unwrap();
+    ///
+    /// match service.try_run(|_| 2) {
+    ///     Err(Error::Unavailable) => (),
+    ///     other => panic!("unexpected result: {:?}", other),
+    /// }
+    /// proceed.send(()).unwrap();
+    /// busy.join().unwrap().unwrap();
+    /// assert_eq!(service.run(|_| 3).unwrap(), 3);
+    /// ```
+    pub fn try_run<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Louis) -> T + Send + 'static,
+    {
+        self.call(f, false)
+    }
+
+    fn call<T, F>(&self, f: F, wait: bool) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Louis) -> T + Send + 'static,
+    {
+        if self.inner.on_worker() {
+            return Err(Error::Unavailable);
+        }
+        let (reply, response) = mpsc::sync_channel(1);
+        let job: Job = Box::new(move |louis| {
+            let _ = reply.send(panic::catch_unwind(AssertUnwindSafe(|| f(louis))));
+        });
+        if wait {
+            self.submit(job)?;
+        } else if let Submitted::Full(_) = self.try_submit(job)? {
+            return Err(Error::Unavailable);
+        }
+        match response.recv() {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(payload)) => panic::resume_unwind(payload),
+            Err(_) => Err(Error::ServiceStopped),
+        }
+    }
+
+    /// Queues `job`, waiting for room in the queue if necessary.
+    fn submit(&self, job: Job) -> Result<()> {
+        self.inner.slots.take()?;
+        self.enqueue(job).map(|_| ())
+    }
+
+    /// Queues `job` if there is room in the queue, and returns it otherwise.
+    pub(crate) fn try_submit(&self, job: Job) -> Result<Submitted> {
+        if self.inner.slots.try_take()? {
+            self.enqueue(job).map(Submitted::Queued)
+        } else {
+            Ok(Submitted::Full(job))
+        }
+    }
+
+    /// Like `try_submit`, but arranges for the task of `cx` to be woken up once there is room.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn poll_submit(&self, job: Job, cx: &mut Context) -> Result<Submitted> {
+        match self.inner.slots.poll_take(cx) {
+            Poll::Ready(taken) => taken.and_then(|()| self.enqueue(job).map(Submitted::Queued)),
+            Poll::Pending => Ok(Submitted::Full(job)),
+        }
+    }
+
+    /// Queues `job` in a slot that has already been taken.
+    fn enqueue(&self, job: Job) -> Result<Arc<Slot>> {
+        let slot = Arc::new(Slot {
+            slots: self.inner.slots.clone(),
+            state: AtomicU8::new(Slot::QUEUED),
+        });
+        match self.inner.sender.send(Message::Job(job, slot.clone())) {
+            Ok(()) => Ok(slot),
+            Err(_) => {
+                slot.cancel();
+                Err(Error::ServiceStopped)
+            }
+        }
+    }
+
+    /// See `Louis::translate_simple`
+    pub fn translate_simple(
+        &self,
+        table_names: &str,
+        input: &str,
+        backwards: bool,
+        mode: TranslationModes,
+    ) -> Result<String> {
+        let (table_names, input) = (table_names.to_owned(), input.to_owned());
+        self.run(move |louis| louis.translate_simple(&table_names, &input, backwards, mode))?
+    }
+
+    /// See `Louis::translate`
+    pub fn translate(
+        &self,
+        table_names: &str,
+        input: &str,
+        mode: TranslationModes,
+    ) -> Result<Translation> {
+        let (table_names, input) = (table_names.to_owned(), input.to_owned());
+        self.run(move |louis| louis.translate(&table_names, &input, mode))?
+    }
+
+    /// See `Louis::translate_with_options`
+    pub fn translate_with_options(
+        &self,
+        table_names: &str,
+        input: &str,
+        mode: TranslationModes,
+        options: &TranslationOptions,
+    ) -> Result<Translation> {
+        let (table_names, input, options) =
+            (table_names.to_owned(), input.to_owned(), options.clone());
+        self.run(move |louis| louis.translate_with_options(&table_names, &input, mode, &options))?
+    }
+
+    /// See `Louis::back_translate`
+    pub fn back_translate(
+        &self,
+        table_names: &str,
+        input: &str,
+        mode: TranslationModes,
+    ) -> Result<Translation> {
+        let (table_names, input) = (table_names.to_owned(), input.to_owned());
+        self.run(move |louis| louis.back_translate(&table_names, &input, mode))?
+    }
+
+    /// See `Louis::back_translate_with_options`
+    pub fn back_translate_with_options(
+        &self,
+        table_names: &str,
+        input: &str,
+        mode: TranslationModes,
+        options: &TranslationOptions,
+    ) -> Result<Translation> {
+        let (table_names, input, options) =
+            (table_names.to_owned(), input.to_owned(), options.clone());
+        self.run(move |louis| {
+            louis.back_translate_with_options(&table_names, &input, mode, &options)
+        })?
+    }
+
+    /// See `Louis::hyphenate`
+    pub fn hyphenate(
+        &self,
+        table_names: &str,
+        word: &str,
+        mode: HyphenationMode,
+    ) -> Result<Vec<usize>> {
+        let (table_names, word) = (table_names.to_owned(), word.to_owned());
+        self.run(move |louis| louis.hyphenate(&table_names, &word, mode))?
+    }
+
+    /// Stops the service once the requests queued so far have been handled, and waits until
+    /// the `Louis` has been dropped. Afterwards, all handles fail with `Error::ServiceStopped`.
+    ///
+    /// When called from a job running on the service, the service stops after that job and
+    /// the requests queued so far, without waiting.
+    ///
+    /// ```
+    /// # use louis::{Error, Louis, LouisService, modes::TranslationModes};
+    /// # use std::sync::mpsc;
+    /// # use std::thread;
+    /// # use std::time::Duration;
+    /// let service = LouisService::new(Louis::new().unwrap(), 3).unwrap();
+    ///
+    /// // Fill the queue behind a job that waits for `proceed` and then stops the service
+    /// let (started, start) = mpsc::channel();
+    /// let (proceed, wait) = mpsc::channel::<()>();
+    /// let handle = service.clone();
+    /// let busy = thread::spawn(move || {
+    ///     let stopper = handle.clone();
+    ///     handle.run(move |_| {
+    ///         started.send(()).unwrap();
+    ///         wait.recv().unwrap();
+    ///         stopper.shutdown();
+    ///     })
+    /// });
+    /// start.recv().unwrap();
+    /// let queued: Vec<_> = (0..3)
+    ///     .map(|i| {
+    ///         let handle = service.clone();
+    ///         thread::spawn(move || handle.run(move |_| i))
+    ///     })
+    ///     .collect();
+    /// # // There is no way to tell when the requests have been queued
+    /// # thread::sleep(Duration::from_millis(200));
+    ///
+    /// // The requests queued before the service was stopped are still handled
+    /// proceed.send(()).unwrap();
+    /// busy.join().unwrap().unwrap();
+    /// for (i, request) in queued.into_iter().enumerate() {
+    ///     assert_eq!(request.join().unwrap().unwrap(), i);
+    /// }
+    /// service.shutdown();
+    ///
+    /// match service.translate_simple("en_US.tbl", "hand", false, TranslationModes::empty()) {
+    ///     Err(Error::ServiceStopped) => (),
+    ///     other => panic!("unexpected result: {:?}", other),
+    /// }
+    /// // liblouis is free to be used again
+    /// assert!(Louis::new().is_some());
+    /// ```
+    pub fn shutdown(&self) {
+        self.inner.shutdown();
+    }
+}
+
+impl Inner {
+    fn on_worker(&self) -> bool {
+        thread::current().id() == self.thread_id
+    }
+
+    fn shutdown(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        self.slots.close();
+        // A job shutting down its own service can't wait for itself,
+        // but the thread checks `stopping` once the job is done
+        if self.on_worker() {
+            return;
+        }
+        let mut thread = self
+            .thread
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        // Fails if the thread is already gone
+        let _ = self.sender.send(Message::Shutdown);
+        if let Some(thread) = thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn serve(mut louis: Louis, receiver: Receiver<Message>, stopping: &AtomicBool) {
+    while !stopping.load(Ordering::SeqCst) {
+        match receiver.recv() {
+            Ok(Message::Job(job, slot)) => handle(&mut louis, job, &slot),
+            Ok(Message::Shutdown) | Err(_) => break,
+        }
+    }
+    // Requests queued before the service was stopped are still handled
+    for message in receiver.try_iter() {
+        if let Message::Job(job, slot) = message {
+            handle(&mut louis, job, &slot);
+        }
+    }
+}
+
+/// Runs `job` unless it was cancelled while it was queued.
+fn handle(louis: &mut Louis, job: Job, slot: &Slot) {
+    if slot.start() {
+        job(louis);
+        slot.finish();
+    }
+}
+
+/// Counts the room in a service's queue. Requests take up a slot from the moment they are queued
+/// until they have been handled, or until they are cancelled, so the room cancelled requests
+/// held is available again right away.
+struct Slots {
+    state: Mutex<SlotsState>,
+    /// Signalled when a slot is freed or the service stops, for blocking requests
+    room: Condvar,
+}
+
+struct SlotsState {
+    free: usize,
+    /// Set once the service has been shut down
+    closed: bool,
+    /// Asynchronous requests waiting for a slot
+    waiting: Vec<Waker>,
+}
+
+impl Slots {
+    fn new(free: usize) -> Self {
+        Slots {
+            state: Mutex::new(SlotsState {
+                free,
+                closed: false,
+                waiting: Vec::new(),
+            }),
+            room: Condvar::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SlotsState> {
+        // The state is never left inconsistent
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Takes a slot, waiting until one is free.
+    fn take(&self) -> Result<()> {
+        let mut state = self.lock();
+        loop {
+            if state.closed {
+                return Err(Error::ServiceStopped);
+            }
+            if state.free > 0 {
+                state.free -= 1;
+                return Ok(());
+            }
+            state = self
+                .room
+                .wait(state)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+
+    /// Takes a slot if one is free.
+    fn try_take(&self) -> Result<bool> {
+        let mut state = self.lock();
+        if state.closed {
+            return Err(Error::ServiceStopped);
+        }
+        if state.free == 0 {
+            return Ok(false);
+        }
+        state.free -= 1;
+        Ok(true)
+    }
+
+    #[cfg(feature = "tokio")]
+    fn poll_take(&self, cx: &mut Context) -> Poll<Result<()>> {
+        let mut state = self.lock();
+        if state.closed {
+            return Poll::Ready(Err(Error::ServiceStopped));
+        }
+        if state.free == 0 {
+            if !state
+                .waiting
+                .iter()
+                .any(|waker| waker.will_wake(cx.waker()))
+            {
+                state.waiting.push(cx.waker().clone());
+            }
+            return Poll::Pending;
+        }
+        state.free -= 1;
+        Poll::Ready(Ok(()))
+    }
+
+    fn give_back(&self) {
+        let waiting = {
+            let mut state = self.lock();
+            state.free += 1;
+            self.room.notify_one();
+            // Whoever doesn't get the slot goes back to waiting
+            mem::take(&mut state.waiting)
+        };
+        for waker in waiting {
+            waker.wake();
+        }
+    }
+
+    fn close(&self) {
+        let waiting = {
+            let mut state = self.lock();
+            state.closed = true;
+            self.room.notify_all();
+            mem::take(&mut state.waiting)
+        };
+        for waker in waiting {
+            waker.wake();
+        }
+    }
+}
+
+/// The slot taken up by a queued request, which is freed exactly once:
+/// when the request has been handled, or when it is cancelled before the thread got to it.
+pub(crate) struct Slot {
+    slots: Arc<Slots>,
+    state: AtomicU8,
+}
+
+impl Slot {
+    const QUEUED: u8 = 0;
+    const STARTED: u8 = 1;
+    const FREED: u8 = 2;
+
+    /// Returns false if the request has been cancelled.
+    fn start(&self) -> bool {
+        self.moved(Slot::QUEUED, Slot::STARTED)
+    }
+
+    fn finish(&self) {
+        if self.moved(Slot::STARTED, Slot::FREED) {
+            self.slots.give_back();
+        }
+    }
+
+    /// Frees the slot, unless the thread has already started on the request.
+    pub(crate) fn cancel(&self) {
+        if self.moved(Slot::QUEUED, Slot::FREED) {
+            self.slots.give_back();
+        }
+    }
+
+    fn moved(&self, from: u8, to: u8) -> bool {
+        self.state
+            .compare_exchange(from, to, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
+}
@@ -0,0 +1,132 @@
+use std::ffi::CString;
+use std::fmt;
+
+use super::modes::{HyphenationMode, TranslationModes};
+use super::{Louis, Result, Translation, TranslationOptions};
+
+/// A list of tables that has been compiled and checked using `Louis::load_table`,
+/// possibly with extra rules added using `with_rules`.
+///
+/// Its methods work like the ones of `Louis` with the same name,
+/// but don't have to look up the tables again on every call.
+#[derive(Clone)]
+pub struct Table<'louis> {
+    louis: &'louis Louis,
+    /// The table list liblouis knows the compiled tables by
+    key: CString,
+    names: String,
+    rules: Vec<String>,
+}
+
+impl<'louis> Table<'louis> {
+    pub(crate) fn new(louis: &'louis Louis, names: CString) -> Self {
+        Table {
+            louis,
+            names: names.to_string_lossy().into_owned(),
+            key: names,
+            rules: Vec::new(),
+        }
+    }
+
+    /// The comma-separated list of table names this was loaded from
+    pub fn names(&self) -> &str {
+        &self.names
+    }
+
+    /// The rules that were added to the tables using `with_rules`
+    pub fn rules(&self) -> &[String] {
+        &self.rules
+    }
+
+    /// Compiles a new table consisting of this one plus the given extra rules, using `lou_compileString`.
+    ///
+    /// liblouis changes compiled tables in place when adding rules, so the new table is compiled
+    /// separately, and neither this table nor other users of the same table list are affected.
+    /// To change the extra rules, call `with_rules` on this table again. Dropping the returned
+    /// table does not reclaim its memory: liblouis keeps every compiled table until `Louis` is
+    /// dropped. Asking for the same rules on the same tables again reuses the table compiled
+    /// the first time, so rebuilding an overlay does not take up more memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use louis::{Louis, modes::TranslationModes};
+    /// let louis = Louis::new().unwrap();
+    /// let base = louis.load_table("en-ueb-g2.ctb").unwrap();
+    /// let custom = base.with_rules(&["word hand 125"]).unwrap();
+    ///
+    /// let text = "A bird in the hand";
+    /// assert_eq!(base.translate(text, TranslationModes::empty()).unwrap().output, ",a bird 9 ! h&");
+    /// assert_eq!(custom.translate(text, TranslationModes::empty()).unwrap().output, ",a bird 9 ! h");
+    /// ```
+    pub fn with_rules<S: AsRef<str>>(&self, rules: &[S]) -> Result<Table<'louis>> {
+        let rules: Vec<String> = self
+            .rules
+            .iter()
+            .cloned()
+            .chain(rules.iter().map(|rule| rule.as_ref().to_owned()))
+            .collect();
+        let key = self.louis.compile_with_rules(&self.names, &rules)?;
+        Ok(Table {
+            louis: self.louis,
+            key,
+            names: self.names.clone(),
+            rules,
+        })
+    }
+
+    /// See `Louis::translate`
+    pub fn translate(&self, input: &str, mode: TranslationModes) -> Result<Translation> {
+        self.translate_with_options(input, mode, &TranslationOptions::default())
+    }
+
+    /// See `Louis::translate_with_options`
+    pub fn translate_with_options(
+        &self,
+        input: &str,
+        mode: TranslationModes,
+        options: &TranslationOptions,
+    ) -> Result<Translation> {
+        self.louis
+            .translate_positions(&self.key, input, false, mode, options)
+    }
+
+    /// See `Louis::translate_batch`
+    pub fn translate_batch<S: AsRef<str>>(
+        &self,
+        inputs: &[S],
+        mode: TranslationModes,
+    ) -> Vec<Result<Translation>> {
+        self.louis.translate_batch_compiled(&self.key, inputs, mode)
+    }
+
+    /// See `Louis::back_translate`
+    pub fn back_translate(&self, input: &str, mode: TranslationModes) -> Result<Translation> {
+        self.back_translate_with_options(input, mode, &TranslationOptions::default())
+    }
+
+    /// See `Louis::back_translate_with_options`
+    pub fn back_translate_with_options(
+        &self,
+        input: &str,
+        mode: TranslationModes,
+        options: &TranslationOptions,
+    ) -> Result<Translation> {
+        self.louis
+            .translate_positions(&self.key, input, true, mode, options)
+    }
+
+    /// See `Louis::hyphenate`
+    pub fn hyphenate(&self, word: &str, mode: HyphenationMode) -> Result<Vec<usize>> {
+        self.louis.hyphenate_word(&self.key, word, mode)
+    }
+}
+
+impl<'louis> fmt::Debug for Table<'louis> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Table")
+            .field("names", &self.names)
+            .field("rules", &self.rules)
+            .finish()
+    }
+}
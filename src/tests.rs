@@ -1,9 +1,9 @@
-use super::Louis;
+use super::modes::TranslationModes;
+use super::{Error, Louis, DEFAULT_MAX_OUTPUT_LEN};
 use assert_cmd::prelude::*;
 use std::process::Command;
 use std::sync::Mutex;
 
-
 lazy_static! {
     static ref API: Mutex<Louis> = Mutex::new(Louis::new().unwrap());
 }
@@ -12,14 +12,42 @@ lazy_static! {
 fn louis_version() {
     use semver::Version;
     let louis = API.lock().unwrap();
-    assert!(louis.version() >= Version::parse("3.0.0"));
+    assert!(louis.version().unwrap() >= Version::parse("3.0.0").unwrap());
 }
 
 #[test]
 fn list_tables() {
+    use super::metadata::{self, TableInfo};
     let louis = API.lock().unwrap();
-    let tables = louis.list_tables();
-    assert!(tables.len() > 0);
+    let mut tables = louis.list_tables().unwrap();
+    assert!(!tables.is_empty());
+    assert!(tables.iter().all(|info| info.path.is_file()));
+    assert!(tables
+        .iter()
+        .any(|info| info.path.file_name().unwrap() == "de_CH.tbl" && info.display_name.is_some()));
+
+    tables.sort_by(TableInfo::cmp_by_language_and_grade);
+    let german: Vec<_> = tables
+        .iter()
+        .filter(|info| info.language().as_deref() == Some("de"))
+        .map(|info| (info.locale.as_ref().unwrap().as_str(), info.grade.unwrap()))
+        .collect();
+    assert_eq!(
+        german,
+        [
+            ("de", 0.0),
+            ("de", 1.0),
+            ("de", 2.0),
+            ("de-CH", 0.0),
+            ("de-CH", 1.0),
+            ("de-CH", 2.0)
+        ]
+    );
+
+    let (count, german_count) = (tables.len(), german.len());
+    let groups = metadata::group_by_language(tables);
+    assert_eq!(groups.values().map(Vec::len).sum::<usize>(), count);
+    assert_eq!(groups[&Some("de".to_owned())].len(), german_count);
 }
 
 #[test]
@@ -27,7 +55,9 @@ fn translate_simple_de() {
     let sentence = "Dies ist ein kurzer Satz.";
     let louis = API.lock().unwrap();
     assert_eq!(
-        louis.translate_simple("de.tbl", sentence, false, 0),
+        louis
+            .translate_simple("de.tbl", sentence, false, TranslationModes::empty())
+            .unwrap(),
         "d0s } 6 kz7 sz."
     );
 }
@@ -37,7 +67,9 @@ fn translate_simple_en() {
     let sentence = "This is an example sentence with a rare word: syzygy.";
     let louis = API.lock().unwrap();
     assert_eq!(
-        louis.translate_simple("en_US.tbl", sentence, false, 0),
+        louis
+            .translate_simple("en_US.tbl", sentence, false, TranslationModes::empty())
+            .unwrap(),
         ",? is an example s5t;e )a r>e ~w3 syzygy4"
     );
 }
@@ -47,7 +79,9 @@ fn translate_simple_escape_umlauts() {
     let sentence = "äöü";
     let louis = API.lock().unwrap();
     assert_eq!(
-        louis.translate_simple("en_US.tbl", sentence, false, 0),
+        louis
+            .translate_simple("en_US.tbl", sentence, false, TranslationModes::empty())
+            .unwrap(),
         "`a`o`u"
     );
 }
@@ -57,7 +91,9 @@ fn translate_simple_miss_everything() {
     let sentence = "はたらく細胞";
     let louis = API.lock().unwrap();
     assert_eq!(
-        louis.translate_simple("en_US.tbl", sentence, false, 0),
+        louis
+            .translate_simple("en_US.tbl", sentence, false, TranslationModes::empty())
+            .unwrap(),
         r"'\x306f''\x305f''\x3089''\x304f''\x7d30''\x80de'"
     );
 }
@@ -67,34 +103,1264 @@ fn translate_simple_dots_unicode() {
     use super::modes::DOTS_UNICODE;
     let sentence = "Turn this sentence into braille dots please!";
     let louis = API.lock().unwrap();
-    assert_eq!(louis.translate_simple("en_US.tbl", sentence, false, DOTS_UNICODE), "⠠⠞⠥⠗⠝⠀⠹⠀⠎⠢⠞⠰⠑⠀⠔⠖⠃⠗⠇⠀⠙⠕⠞⠎⠀⠏⠇⠂⠎⠑⠖");
+    assert_eq!(
+        louis
+            .translate_simple("en_US.tbl", sentence, false, DOTS_UNICODE)
+            .unwrap(),
+        "⠠⠞⠥⠗⠝⠀⠹⠀⠎⠢⠞⠰⠑⠀⠔⠖⠃⠗⠇⠀⠙⠕⠞⠎⠀⠏⠇⠂⠎⠑⠖"
+    );
 }
 
 #[test]
 fn translate_simple_empty() {
     let sentence = "";
     let louis = API.lock().unwrap();
-    assert_eq!(louis.translate_simple("de.tbl", sentence, false, 0), "");
+    assert_eq!(
+        louis
+            .translate_simple("de.tbl", sentence, false, TranslationModes::empty())
+            .unwrap(),
+        ""
+    );
+}
+
+#[test]
+fn translate_matches_translate_simple() {
+    let sentence = "This is an example sentence with a rare word: syzygy.";
+    let louis = API.lock().unwrap();
+    let translation = louis
+        .translate("en_US.tbl", sentence, TranslationModes::empty())
+        .unwrap();
+    assert_eq!(
+        translation.output,
+        louis
+            .translate_simple("en_US.tbl", sentence, false, TranslationModes::empty())
+            .unwrap()
+    );
+    assert_eq!(translation.output_pos.len(), sentence.chars().count());
+    assert_eq!(
+        translation.input_pos.len(),
+        translation.output.chars().count()
+    );
+}
+
+#[test]
+fn translate_positions_de() {
+    let louis = API.lock().unwrap();
+    let translation = louis
+        .translate("de.tbl", "Dies ist", TranslationModes::empty())
+        .unwrap();
+    assert_eq!(translation.output, "d0s }");
+    assert_eq!(translation.output_pos, vec![0, 0, 0, 0, 3, 4, 4, 4]);
+    assert_eq!(translation.input_pos, vec![0, 0, 0, 4, 5]);
+}
+
+#[test]
+fn translate_empty() {
+    let louis = API.lock().unwrap();
+    let translation = louis
+        .translate("de.tbl", "", TranslationModes::empty())
+        .unwrap();
+    assert_eq!(translation.output, "");
+    assert!(translation.output_pos.is_empty());
+    assert!(translation.input_pos.is_empty());
+}
+
+#[test]
+fn translate_invalid_table() {
+    let louis = API.lock().unwrap();
+    match louis.translate("no_such_table.utb", "Hello", TranslationModes::empty()) {
+        Err(Error::InvalidTable(e)) => assert_eq!(e.table_names, "no_such_table.utb"),
+        other => panic!("expected InvalidTable, got {:?}", other),
+    }
+}
+
+#[test]
+fn translate_nul_in_input() {
+    let louis = API.lock().unwrap();
+    match louis.translate_simple(
+        "en_US.tbl",
+        "Hello\0World",
+        false,
+        TranslationModes::empty(),
+    ) {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("expected InvalidInput, got {:?}", other),
+    }
+    match louis.translate_simple("en_US.tbl\0", "Hello", false, TranslationModes::empty()) {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("expected InvalidInput, got {:?}", other),
+    }
+}
+
+#[test]
+fn translate_long_input() {
+    let sentence = "äöü".repeat(10000);
+    let louis = API.lock().unwrap();
+    let translation = louis
+        .translate("en_US.tbl", &sentence, TranslationModes::empty())
+        .unwrap();
+    assert_eq!(translation.output, "`a`o`u".repeat(10000));
+}
+
+#[test]
+fn translate_output_around_half_capacity() {
+    use super::OUTLEN_MULTIPLIER;
+    // Each of these characters becomes 8 characters of output, so mixing them with letters
+    // can put the output right below or above half of the initial buffer capacity
+    let escaped = r"'\x306f'";
+    let louis = API.lock().unwrap();
+    for &offset in &[-1, 1] {
+        let (escapes, letters) = (1..20)
+            .flat_map(|escapes| (1..20).map(move |letters| (escapes, letters)))
+            .find(|&(escapes, letters)| {
+                let half = (escapes + letters) * OUTLEN_MULTIPLIER / 2;
+                (escapes * escaped.len() + letters) as isize == half as isize + offset
+            })
+            .unwrap();
+        let input = format!("{}{}", "は".repeat(escapes), "a".repeat(letters));
+        let expected = format!("{}{}", escaped.repeat(escapes), "a".repeat(letters));
+        let translation = louis
+            .translate("en_US.tbl", &input, TranslationModes::empty())
+            .unwrap();
+        assert_eq!(translation.output, expected);
+        assert_eq!(translation.input_pos.len(), expected.len());
+    }
+}
+
+#[test]
+fn translate_output_limit() {
+    // Every character gets escaped as '\xhhhh', making the output 8 times as long
+    let sentence = "はたらく細胞";
+    let mut louis = API.lock().unwrap();
+    louis.set_max_output_len(47);
+    let truncated = louis.translate_simple("en_US.tbl", sentence, false, TranslationModes::empty());
+    louis.set_max_output_len(48);
+    let fits = louis.translate_simple("en_US.tbl", sentence, false, TranslationModes::empty());
+    louis.set_max_output_len(DEFAULT_MAX_OUTPUT_LEN);
+    match truncated {
+        Err(Error::OutputTruncated) => (),
+        other => panic!("expected OutputTruncated, got {:?}", other),
+    }
+    assert_eq!(fits.unwrap().chars().count(), 48);
+}
+
+#[test]
+fn translation_modes_from_bits() {
+    use super::modes::DOTS_UNICODE;
+    use std::convert::TryFrom;
+    assert_eq!(TranslationModes::try_from(4 | 64).unwrap(), DOTS_UNICODE);
+    match TranslationModes::try_from(8) {
+        Err(Error::InvalidMode(8)) => (),
+        other => panic!("expected InvalidMode, got {:?}", other),
+    }
+}
+
+#[test]
+fn translation_modes_debug() {
+    let mode = TranslationModes::NO_UNDEFINED_DOTS | TranslationModes::COMPBRL_LEFT_CURSOR;
+    assert_eq!(
+        format!("{:?}", mode),
+        "COMPBRL_LEFT_CURSOR | NO_UNDEFINED_DOTS"
+    );
+    assert_eq!(format!("{:?}", TranslationModes::empty()), "(empty)");
+}
+
+#[test]
+fn translate_emphasis() {
+    use super::emphasis::{Emphasis, EmphasisSpan};
+    use super::TranslationOptions;
+    let louis = API.lock().unwrap();
+    let options = TranslationOptions {
+        emphasis: vec![
+            EmphasisSpan::new(0..4, Emphasis::Italic),
+            EmphasisSpan::new(8..12, Emphasis::Emph(3)),
+        ],
+        cursor: None,
+        hyphens: None,
+    };
+    let translation = louis
+        .translate_with_options(
+            "en-ueb-g1.ctb",
+            "This is bold",
+            TranslationModes::empty(),
+            &options,
+        )
+        .unwrap();
+    assert_eq!(translation.output, ".1,this is ~1bold");
+}
+
+#[test]
+fn translate_emphasis_invalid() {
+    use super::emphasis::{Emphasis, EmphasisSpan};
+    use super::TranslationOptions;
+    let louis = API.lock().unwrap();
+    for span in &[
+        EmphasisSpan::new(3..20, Emphasis::Bold),
+        EmphasisSpan::new(0..1, Emphasis::Emph(11)),
+    ] {
+        let options = TranslationOptions {
+            emphasis: vec![span.clone()],
+            cursor: None,
+            hyphens: None,
+        };
+        match louis.translate_with_options(
+            "en-ueb-g1.ctb",
+            "Hello",
+            TranslationModes::empty(),
+            &options,
+        ) {
+            Err(Error::InvalidInput(_)) => (),
+            other => panic!("expected InvalidInput, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn emphasis_classes() {
+    let louis = API.lock().unwrap();
+    let classes = louis.emphasis_classes("en-ueb-g1.ctb").unwrap();
+    assert_eq!(
+        classes,
+        vec![
+            "italic",
+            "underline",
+            "bold",
+            "script",
+            "transnote",
+            "trans1",
+            "trans2",
+            "trans3",
+            "trans4",
+            "trans5"
+        ]
+    );
+    for (i, class) in classes.iter().enumerate() {
+        assert_eq!(
+            louis
+                .emphasis_class_typeform("en-ueb-g1.ctb", class)
+                .unwrap(),
+            1 << i
+        );
+    }
+}
+
+#[test]
+fn translate_emphasis_class() {
+    use super::emphasis::{Emphasis, EmphasisSpan};
+    use super::TranslationOptions;
+    let louis = API.lock().unwrap();
+    let by_class = TranslationOptions {
+        emphasis: vec![EmphasisSpan::new(0..4, Emphasis::Class("script".into()))],
+        cursor: None,
+        hyphens: None,
+    };
+    let by_number = TranslationOptions {
+        emphasis: vec![EmphasisSpan::new(0..4, Emphasis::Emph(4))],
+        cursor: None,
+        hyphens: None,
+    };
+    let translate = |options| {
+        louis
+            .translate_with_options("en-ueb-g1.ctb", "word", TranslationModes::empty(), options)
+            .unwrap()
+            .output
+    };
+    assert_eq!(translate(&by_class), translate(&by_number));
+
+    let unknown = TranslationOptions {
+        emphasis: vec![EmphasisSpan::new(0..4, Emphasis::Class("sparkly".into()))],
+        cursor: None,
+        hyphens: None,
+    };
+    match louis.translate_with_options("en-ueb-g1.ctb", "word", TranslationModes::empty(), &unknown)
+    {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("expected InvalidInput, got {:?}", other),
+    }
+}
+
+#[test]
+fn translate_cursor() {
+    use super::TranslationOptions;
+    let louis = API.lock().unwrap();
+    let input = "the handstand";
+    let translate = |cursor, mode| {
+        let options = TranslationOptions {
+            emphasis: vec![],
+            cursor: Some(cursor),
+            hyphens: None,
+        };
+        louis
+            .translate_with_options("en_US.tbl", input, mode, &options)
+            .unwrap()
+    };
+
+    let contracted = translate(10, TranslationModes::empty());
+    assert_eq!(contracted.output, "! h&/&");
+    assert_eq!(contracted.cursor, Some(5));
+
+    let at_cursor = translate(10, TranslationModes::COMPBRL_AT_CURSOR);
+    assert_eq!(at_cursor.output, "! handstand");
+    assert_eq!(at_cursor.cursor, Some(8));
+
+    let left_of_cursor = translate(10, TranslationModes::COMPBRL_LEFT_CURSOR);
+    assert_eq!(left_of_cursor.output, "! handst&");
+
+    let at_end = translate(input.len(), TranslationModes::COMPBRL_AT_CURSOR);
+    assert_eq!(at_end.output, "! h&/&");
+    assert_eq!(at_end.cursor, Some(6));
+
+    let options = TranslationOptions {
+        emphasis: vec![],
+        cursor: Some(input.len() + 1),
+        hyphens: None,
+    };
+    match louis.translate_with_options("en_US.tbl", input, TranslationModes::empty(), &options) {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("expected InvalidInput, got {:?}", other),
+    }
+}
+
+#[test]
+fn back_translate_positions() {
+    let louis = API.lock().unwrap();
+    let translation = louis
+        .back_translate("en_US.tbl", ",x \"ws6", TranslationModes::empty())
+        .unwrap();
+    assert_eq!(translation.output, "It works!");
+    assert_eq!(translation.output_pos, [0, 0, 2, 3, 3, 7, 8]);
+    assert_eq!(translation.input_pos, [0, 0, 2, 3, 3, 3, 3, 5, 6]);
+    assert_eq!(translation.cursor, None);
+}
+
+#[test]
+fn back_translate_matches_translate_simple() {
+    let louis = API.lock().unwrap();
+    let input = ",! qk br{n fox jumps ov} ! lazy dog4";
+    let simple = louis
+        .translate_simple("en_US.tbl", input, true, TranslationModes::empty())
+        .unwrap();
+    let translation = louis
+        .back_translate("en_US.tbl", input, TranslationModes::empty())
+        .unwrap();
+    assert_eq!(translation.output, simple);
+    assert_eq!(translation.output_pos.len(), input.chars().count());
+    assert_eq!(translation.input_pos.len(), simple.chars().count());
+}
+
+#[test]
+fn back_translate_cursor() {
+    use super::TranslationOptions;
+    let louis = API.lock().unwrap();
+    let options = TranslationOptions {
+        emphasis: vec![],
+        cursor: Some(4),
+        hyphens: None,
+    };
+    let translation = louis
+        .back_translate_with_options("en_US.tbl", "! h&/&", TranslationModes::empty(), &options)
+        .unwrap();
+    assert_eq!(translation.output, "the handstand");
+    assert_eq!(translation.cursor, Some(8));
+}
+
+#[test]
+fn back_translate_without_output() {
+    use super::TranslationOptions;
+    let louis = API.lock().unwrap();
+    // liblouis has no rules for dots 7 and 8 in en_US.tbl, and drops them in this mode
+    let options = TranslationOptions {
+        emphasis: vec![],
+        cursor: Some(1),
+        hyphens: None,
+    };
+    let translation = louis
+        .back_translate_with_options(
+            "en_US.tbl",
+            "\u{28ff}\u{28c0}",
+            TranslationModes::NO_UNDEFINED_DOTS,
+            &options,
+        )
+        .unwrap();
+    assert_eq!(translation.output, "");
+    assert_eq!(translation.output_pos, [0, 0]);
+    assert!(translation.input_pos.is_empty());
+    assert_eq!(translation.cursor, Some(0));
+}
+
+#[test]
+fn back_translate_rejects_emphasis() {
+    use super::emphasis::{Emphasis, EmphasisSpan};
+    use super::TranslationOptions;
+    let louis = API.lock().unwrap();
+    let options = TranslationOptions {
+        emphasis: vec![EmphasisSpan::new(0..1, Emphasis::Bold)],
+        cursor: None,
+        hyphens: None,
+    };
+    match louis.back_translate_with_options("en_US.tbl", ",x", TranslationModes::empty(), &options)
+    {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("expected InvalidInput, got {:?}", other),
+    }
+}
+
+#[test]
+fn hyphenate_print() {
+    use super::modes::HyphenationMode;
+    let louis = API.lock().unwrap();
+    let breaks = louis
+        .hyphenate("en_US.tbl", "\"Hyphenation,\"", HyphenationMode::Print)
+        .unwrap();
+    assert_eq!(breaks, [3, 7]);
+}
+
+#[test]
+fn hyphenate_braille() {
+    use super::modes::HyphenationMode;
+    let louis = API.lock().unwrap();
+    let braille = louis
+        .translate_simple("en_US.tbl", "hyphenation", false, TranslationModes::empty())
+        .unwrap();
+    assert_eq!(braille, "hyph5,n");
+    let breaks = louis
+        .hyphenate("en_US.tbl", &braille, HyphenationMode::Braille)
+        .unwrap();
+    assert_eq!(breaks, [2, 6]);
+}
+
+#[test]
+fn hyphenate_errors() {
+    use super::modes::HyphenationMode;
+    use super::MAX_HYPHENATION_LEN;
+    let louis = API.lock().unwrap();
+    let print = HyphenationMode::Print;
+    match louis.hyphenate("en_US.tbl", "two words", print) {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("expected InvalidInput, got {:?}", other),
+    }
+    let long_word = "a".repeat(MAX_HYPHENATION_LEN);
+    match louis.hyphenate("en_US.tbl", &long_word, print) {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("expected InvalidInput, got {:?}", other),
+    }
+    // Neither of these includes a dictionary, even indirectly
+    for &table in &["en-ueb-g2.ctb", "de-de-comp8.ctb"] {
+        for &word in &["hyphenation", "two words"] {
+            match louis.hyphenate(table, word, print) {
+                Err(Error::NoHyphenation(_)) => (),
+                other => panic!("expected NoHyphenation, got {:?}", other),
+            }
+        }
+    }
+    // The dictionary may also be given as part of the table list
+    assert_eq!(
+        louis
+            .hyphenate("en-ueb-g2.ctb,hyph_en_US.dic", "hyphenation", print)
+            .unwrap(),
+        [2, 6]
+    );
+    match louis.hyphenate("en-ueb-g2.ctb,hyph_en_US.dic", "two words", print) {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("expected InvalidInput, got {:?}", other),
+    }
+    // Tables with extra rules still know about their dictionary
+    let overlay = louis
+        .load_table("en-ueb-g2.ctb,hyph_en_US.dic")
+        .unwrap()
+        .with_rules(&["word hand 125"])
+        .unwrap();
+    assert_eq!(overlay.hyphenate("hyphenation", print).unwrap(), [2, 6]);
+    match overlay.hyphenate("two words", print) {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("expected InvalidInput, got {:?}", other),
+    }
+    let overlay = louis
+        .load_table("en-ueb-g2.ctb")
+        .unwrap()
+        .with_rules(&["word hand 125"])
+        .unwrap();
+    match overlay.hyphenate("two words", print) {
+        Err(Error::NoHyphenation(table_names)) => assert_eq!(table_names, "en-ueb-g2.ctb"),
+        other => panic!("expected NoHyphenation, got {:?}", other),
+    }
+    match louis.hyphenate("no_such_table.utb", "hyphenation", print) {
+        Err(Error::InvalidTable(_)) => (),
+        other => panic!("expected InvalidTable, got {:?}", other),
+    }
+}
+
+#[test]
+fn translate_prehyphenated() {
+    use super::modes::HyphenationMode;
+    use super::TranslationOptions;
+    let louis = API.lock().unwrap();
+    let input = "unbelievable";
+    let breaks = louis
+        .hyphenate("en_US.tbl", input, HyphenationMode::Print)
+        .unwrap();
+    assert_eq!(breaks, [2, 4, 8]);
+    let options = TranslationOptions {
+        emphasis: vec![],
+        cursor: None,
+        hyphens: Some(breaks),
+    };
+    let translation = louis
+        .translate_with_options("en_US.tbl", input, TranslationModes::empty(), &options)
+        .unwrap();
+    assert_eq!(translation.output, "unbelieva#");
+    assert_eq!(translation.hyphens, Some(vec![2, 4, 8]));
+
+    let plain = louis
+        .translate("en_US.tbl", input, TranslationModes::empty())
+        .unwrap();
+    assert_eq!(plain.output, translation.output);
+    assert_eq!(plain.hyphens, None);
+}
+
+#[test]
+fn translate_prehyphenated_invalid() {
+    use super::TranslationOptions;
+    let louis = API.lock().unwrap();
+    let options = TranslationOptions {
+        emphasis: vec![],
+        cursor: None,
+        hyphens: Some(vec![4]),
+    };
+    match louis.translate_with_options("en_US.tbl", "word", TranslationModes::empty(), &options) {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("expected InvalidInput, got {:?}", other),
+    }
+    match louis.translate_with_options(
+        "no_such_table.utb",
+        "words",
+        TranslationModes::empty(),
+        &options,
+    ) {
+        Err(Error::InvalidTable(_)) => (),
+        other => panic!("expected InvalidTable, got {:?}", other),
+    }
+    match louis.back_translate_with_options(
+        "en_US.tbl",
+        "w\"d",
+        TranslationModes::empty(),
+        &options,
+    ) {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("expected InvalidInput, got {:?}", other),
+    }
+}
+
+#[test]
+fn char_to_dots_roundtrip() {
+    use super::braille::BrailleCell;
+    let louis = API.lock().unwrap();
+    let text = "Hello, World!";
+    let cells = louis.char_to_dots("en-us-comp8.ctb", text).unwrap();
+    assert_eq!(cells.len(), text.len());
+    assert_eq!(cells[1], BrailleCell::DOT_1 | BrailleCell::DOT_5);
+    assert_eq!(louis.dots_to_char("en-us-comp8.ctb", &cells).unwrap(), text);
+
+    assert_eq!(louis.char_to_dots("en-us-comp8.ctb", "").unwrap(), []);
+    assert_eq!(louis.dots_to_char("en-us-comp8.ctb", &[]).unwrap(), "");
+    match louis.char_to_dots("no_such_table.utb", "a") {
+        Err(Error::InvalidTable(_)) => (),
+        other => panic!("expected InvalidTable, got {:?}", other),
+    }
+    match louis.dots_to_char("no_such_table.utb", &[]) {
+        Err(Error::InvalidTable(_)) => (),
+        other => panic!("expected InvalidTable, got {:?}", other),
+    }
+}
+
+#[test]
+fn braille_cell_louis_dots() {
+    use super::braille::BrailleCell;
+    let cell = BrailleCell::DOT_1 | BrailleCell::DOT_4 | BrailleCell::DOT_5;
+    assert_eq!(cell.to_louis_dots(), 0x8019);
+    assert_eq!(BrailleCell::from_louis_dots(0x8019), Some(cell));
+    assert_eq!(
+        BrailleCell::from_louis_dots(0x8000),
+        Some(BrailleCell::empty())
+    );
+    assert_eq!(BrailleCell::from_louis_dots('a' as _), None);
+}
+
+#[test]
+fn braille_string_conversions() {
+    use super::braille::{BrailleCell, BrailleString};
+    let braille = BrailleString::from_dot_numbers("145-0-12345678-1").unwrap();
+    assert_eq!(braille.len(), 4);
+    assert_eq!(braille[1], BrailleCell::empty());
+    assert_eq!(braille[2], BrailleCell::all());
+    assert_eq!(braille.to_dot_numbers(), "145-0-12345678-1");
+    assert_eq!(braille.to_unicode(), "⠙⠀⣿⠁");
+    assert_eq!(braille.to_string(), "⠙⠀⣿⠁");
+    assert_eq!(BrailleString::from_unicode("⠙⠀⣿⠁").unwrap(), braille);
+    assert_eq!(braille.to_louis_dots(), [0x8019, 0x8000, 0x80ff, 0x8001]);
+    assert_eq!(
+        BrailleString::from_louis_dots(&braille.to_louis_dots()).unwrap(),
+        braille
+    );
+    assert_eq!(
+        BrailleString::from_dot_numbers("").unwrap(),
+        BrailleString::new()
+    );
+
+    for invalid in &["9", "1--2", "11", "12-", "a"] {
+        match BrailleString::from_dot_numbers(invalid) {
+            Err(Error::InvalidInput(_)) => (),
+            other => panic!("expected InvalidInput for {:?}, got {:?}", invalid, other),
+        }
+    }
+    match BrailleString::from_unicode("⠙a") {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("expected InvalidInput, got {:?}", other),
+    }
+    match BrailleString::from_louis_dots(&['a' as _]) {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("expected InvalidInput, got {:?}", other),
+    }
+}
+
+#[test]
+fn braille_string_ascii() {
+    use super::braille::BrailleString;
+    let louis = API.lock().unwrap();
+    let braille = BrailleString::from_unicode("⠠⠹⠁⠞").unwrap();
+    let ascii = braille.to_ascii(&louis, "en-us-comp8.ctb").unwrap();
+    assert_eq!(ascii, ",?at");
+    assert_eq!(
+        BrailleString::from_ascii(&louis, "en-us-comp8.ctb", &ascii).unwrap(),
+        braille
+    );
+}
+
+#[test]
+fn translate_braille() {
+    use super::modes::{DOTS_LOUIS, DOTS_UNICODE};
+    let louis = API.lock().unwrap();
+    let input = "A bird in the hand";
+    let unicode = louis.translate("en_US.tbl", input, DOTS_UNICODE).unwrap();
+    let louis_dots = louis.translate("en_US.tbl", input, DOTS_LOUIS).unwrap();
+    let plain = louis
+        .translate("en_US.tbl", input, TranslationModes::empty())
+        .unwrap();
+    let braille = unicode.braille.unwrap();
+    assert_eq!(braille.to_unicode(), unicode.output);
+    assert_eq!(louis_dots.braille, Some(braille));
+    assert_eq!(plain.braille, None);
+}
+
+#[test]
+fn load_table() {
+    use super::modes::HyphenationMode;
+    let louis = API.lock().unwrap();
+    let table = louis.load_table("unicode.dis,en_US.tbl").unwrap();
+    assert_eq!(table.names(), "unicode.dis,en_US.tbl");
+
+    let input = "A bird in the hand";
+    let forward = table.translate(input, TranslationModes::empty()).unwrap();
+    assert_eq!(
+        forward,
+        louis
+            .translate("unicode.dis,en_US.tbl", input, TranslationModes::empty())
+            .unwrap()
+    );
+    let backward = table
+        .back_translate(&forward.output, TranslationModes::empty())
+        .unwrap();
+    assert_eq!(backward.output, input);
+    assert_eq!(
+        table
+            .hyphenate("hyphenation", HyphenationMode::Print)
+            .unwrap(),
+        [2, 6]
+    );
+}
+
+#[test]
+fn load_table_invalid() {
+    let louis = API.lock().unwrap();
+    for names in &["no_such_table.utb", "en_US.tbl,no_such_table.utb", ""] {
+        match louis.load_table(names) {
+            Err(Error::InvalidTable(ref e)) if e.table_names == *names => (),
+            other => panic!("expected InvalidTable for {:?}, got {:?}", names, other),
+        }
+    }
+    match louis.load_table("en_US.tbl\0") {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("expected InvalidInput, got {:?}", other),
+    }
+}
+
+#[test]
+fn table_compile_diagnostics() {
+    use super::Severity;
+    use std::fs;
+    let louis = API.lock().unwrap();
+    let path = std::env::temp_dir().join("louis-rs-broken.tbl");
+    fs::write(&path, "# broken on purpose\nnosuchopcode a 1\nalways\n").unwrap();
+    let result = louis.load_table(path.to_str().unwrap());
+    fs::remove_file(&path).unwrap();
+
+    let e = match result {
+        Err(Error::InvalidTable(e)) => e,
+        other => panic!("expected InvalidTable, got {:?}", other),
+    };
+    let errors: Vec<_> = e.errors().collect();
+    assert_eq!(errors[0].file.as_ref(), Some(&path));
+    assert_eq!(errors[0].line, Some(2));
+    assert_eq!(errors[0].message, "opcode 'nosuchopcode' not defined.");
+    assert_eq!(errors[1].line, Some(3));
+    assert!(e.diagnostics.iter().all(|d| d.severity == Severity::Error));
+    assert!(e
+        .to_string()
+        .contains(":2: error: opcode 'nosuchopcode' not defined."));
+}
+
+#[test]
+fn table_compile_diagnostics_missing_table() {
+    let louis = API.lock().unwrap();
+    match louis.load_table("no_such_table.utb") {
+        Err(Error::InvalidTable(e)) => {
+            assert!(e.errors().count() > 0);
+            assert!(e.diagnostics.iter().all(|d| d.file.is_none()));
+        }
+        other => panic!("expected InvalidTable, got {:?}", other),
+    }
+}
+
+#[test]
+fn diagnostic_parse() {
+    use super::{Diagnostic, Severity};
+    use louis_sys::{logLevels_LOG_ERROR, logLevels_LOG_INFO, logLevels_LOG_WARN};
+    use std::path::PathBuf;
+    let diagnostic = Diagnostic::parse(
+        logLevels_LOG_WARN,
+        "C:\\tables\\x.tbl:7: warning: rule ignored",
+    )
+    .unwrap();
+    assert_eq!(
+        diagnostic,
+        Diagnostic {
+            severity: Severity::Warning,
+            file: Some(PathBuf::from("C:\\tables\\x.tbl")),
+            line: Some(7),
+            message: "rule ignored".to_owned(),
+        }
+    );
+    assert_eq!(
+        diagnostic.to_string(),
+        "C:\\tables\\x.tbl:7: warning: rule ignored"
+    );
+
+    let diagnostic = Diagnostic::parse(logLevels_LOG_ERROR, "error: out of memory").unwrap();
+    assert_eq!(diagnostic.file, None);
+    assert_eq!(diagnostic.message, "out of memory");
+    let diagnostic = Diagnostic::parse(logLevels_LOG_ERROR, "x.tbl could not be compiled").unwrap();
+    assert_eq!(diagnostic.severity, Severity::Error);
+    assert_eq!(diagnostic.message, "x.tbl could not be compiled");
+    assert_eq!(
+        Diagnostic::parse(logLevels_LOG_INFO, "found table x.tbl"),
+        None
+    );
+}
+
+#[test]
+fn table_with_rules() {
+    let louis = API.lock().unwrap();
+    let mode = TranslationModes::empty();
+    let base = louis.load_table("en-ueb-g2.ctb").unwrap();
+    let text = "the hand and the foot";
+    let plain = base.translate(text, mode).unwrap().output;
+    assert_eq!(plain, "! h& & ! foot");
+
+    let overlay = base.with_rules(&["word hand 125"]).unwrap();
+    assert_eq!(overlay.names(), "en-ueb-g2.ctb");
+    assert_eq!(overlay.rules(), ["word hand 125"]);
+    assert_eq!(
+        overlay.translate(text, mode).unwrap().output,
+        "! h & ! foot"
+    );
+
+    // Rules stack, and the base stays untouched
+    let stacked = overlay.with_rules(&["word foot 124"]).unwrap();
+    assert_eq!(stacked.translate(text, mode).unwrap().output, "! h & ! f");
+    assert_eq!(base.translate(text, mode).unwrap().output, plain);
+    assert_eq!(
+        louis.translate("en-ueb-g2.ctb", text, mode).unwrap().output,
+        plain
+    );
+
+    // Rebuilding from the base discards the earlier rules
+    let rebuilt = base.with_rules(&["word foot 124"]).unwrap();
+    assert_eq!(rebuilt.translate(text, mode).unwrap().output, "! h& & ! f");
+    assert_eq!(
+        overlay.translate(text, mode).unwrap().output,
+        "! h & ! foot"
+    );
+
+    // The same rules on the same tables reuse the compiled table, whose marker file is gone
+    let rules = ["word foot 124".to_owned()];
+    let key = louis.compile_with_rules("en-ueb-g2.ctb", &rules).unwrap();
+    assert_eq!(
+        louis.compile_with_rules("en-ueb-g2.ctb", &rules).unwrap(),
+        key
+    );
+    let marker = key.to_str().unwrap().rsplit(',').next().unwrap();
+    assert!(marker.ends_with(".tbl"));
+    assert!(!std::path::Path::new(marker).exists());
+}
+
+#[test]
+fn table_with_invalid_rules() {
+    let louis = API.lock().unwrap();
+    let base = louis.load_table("en_US.tbl").unwrap();
+    match base.with_rules(&["nosuchopcode a 1"]) {
+        Err(Error::InvalidTable(e)) => {
+            assert_eq!(e.table_names, "en_US.tbl");
+            let error = e.errors().next().unwrap();
+            assert!(error.message.contains("nosuchopcode"));
+            assert_eq!(error.file, None);
+        }
+        other => panic!("expected InvalidTable, got {:?}", other),
+    }
+    for rule in &["word a\x001".to_owned(), "#".repeat(super::MAX_RULE_LEN)] {
+        match base.with_rules(&[rule]) {
+            Err(Error::InvalidInput(_)) => (),
+            other => panic!("expected InvalidInput, got {:?}", other),
+        }
+    }
+    // Failed overlays don't leave anything behind
+    assert_eq!(
+        base.translate("the hand", TranslationModes::empty())
+            .unwrap()
+            .output,
+        "! h&"
+    );
+}
+
+#[test]
+fn table_dirs() {
+    use std::fs;
+    use std::path::Path;
+    let mut louis = API.lock().unwrap();
+    let dir = std::env::temp_dir().join("louis-rs-table-dirs");
+    let data_dir = dir.join("data");
+    fs::create_dir_all(data_dir.join("liblouis").join("tables")).unwrap();
+    fs::write(
+        dir.join("louis-rs-dirs.ctb"),
+        "include en-ueb-g1.ctb\nword foot 124\n",
+    )
+    .unwrap();
+    fs::write(
+        data_dir
+            .join("liblouis")
+            .join("tables")
+            .join("louis-rs-data.ctb"),
+        "include en-ueb-g1.ctb\nword hand 125\n",
+    )
+    .unwrap();
+
+    let text = "hand and foot";
+    assert!(louis.load_table("louis-rs-dirs.ctb").is_err());
+    louis.set_table_dirs(&[&data_dir, &dir]).unwrap();
+    assert_eq!(louis.table_dirs(), [data_dir.clone(), dir.clone()]);
+    // liblouis' data path holds the list of directories, which isn't a path
+    assert_eq!(louis.data_path(), None);
+    let table = louis.load_table("louis-rs-dirs.ctb").unwrap();
+    assert_eq!(
+        table
+            .translate(text, TranslationModes::empty())
+            .unwrap()
+            .output,
+        "hand and f"
+    );
+
+    louis.set_data_path(&data_dir).unwrap();
+    assert_eq!(louis.data_path(), Some(data_dir.clone()));
+    assert!(louis.table_dirs().is_empty());
+    let table = louis.load_table("louis-rs-data.ctb").unwrap();
+    assert_eq!(
+        table
+            .translate(text, TranslationModes::empty())
+            .unwrap()
+            .output,
+        "h and foot"
+    );
+
+    match louis.set_table_dirs(&[dir.join("missing")]) {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("expected InvalidInput, got {:?}", other),
+    }
+    match louis.set_data_path(dir.join("louis-rs-dirs.ctb")) {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("expected InvalidInput, got {:?}", other),
+    }
+    let comma = dir.join("a,b");
+    fs::create_dir_all(&comma).unwrap();
+    match louis.set_table_dirs(&[&comma]) {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("expected InvalidInput, got {:?}", other),
+    }
+    // Failed attempts leave the data path alone
+    assert_eq!(louis.data_path(), Some(data_dir));
+    assert!(louis.table_dirs().is_empty());
+
+    louis.set_table_dirs(&[&dir]).unwrap();
+    assert_eq!(louis.table_dirs(), std::slice::from_ref(&dir));
+    louis.set_table_dirs::<&Path>(&[]).unwrap();
+    assert!(louis.table_dirs().is_empty());
+    assert_eq!(louis.data_path(), None);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn table_resolver() {
+    use super::TableSource;
+    use std::fs;
+    use std::sync::Arc;
+    let mut louis = API.lock().unwrap();
+    let part = std::env::temp_dir().join("louis-rs-resolver-part.utb");
+    fs::write(&part, "include en-ueb-g1.ctb\nword hand 125\n").unwrap();
+
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let resolver_calls = calls.clone();
+    let resolver_part = part.clone();
+    louis.set_table_resolver(move |tables: &str, base: Option<&str>| {
+        resolver_calls
+            .lock()
+            .unwrap()
+            .push((tables.to_owned(), base.map(str::to_owned)));
+        match tables {
+            "louis-rs-virtual.utb" | "louis-rs-virtual-2.utb" => {
+                Some(vec![TableSource::Contents {
+                    name: "virtual".to_owned(),
+                    contents: b"include louis-rs-part.utb\nword foot 124\n".to_vec(),
+                }])
+            }
+            "louis-rs-part.utb" => Some(vec![TableSource::File(resolver_part.clone())]),
+            "louis-rs-empty.utb" => Some(vec![]),
+            "louis-rs-panic.utb" => panic!("no tables here"),
+            _ => None,
+        }
+    });
+
+    let table = louis.load_table("louis-rs-virtual.utb").unwrap();
+    assert_eq!(
+        table
+            .translate("hand and foot", TranslationModes::empty())
+            .unwrap()
+            .output,
+        "h and f"
+    );
+    {
+        let calls = calls.lock().unwrap();
+        assert!(calls.contains(&("louis-rs-part.utb".to_owned(), Some("virtual".to_owned()))));
+        let part_str = part.to_str().unwrap().to_owned();
+        assert!(calls.contains(&("en-ueb-g1.ctb".to_owned(), Some(part_str))));
+    }
+
+    // Tables with the same name and contents are written to the same file
+    louis.load_table("louis-rs-virtual-2.utb").unwrap();
+    let contents_dir = std::env::temp_dir().join(format!("louis-tables-{}", std::process::id()));
+    assert_eq!(fs::read_dir(&contents_dir).unwrap().count(), 1);
+
+    for &(tables, message) in &[
+        ("louis-rs-empty.utb", "no tables"),
+        ("louis-rs-panic.utb", "no tables here"),
+    ] {
+        match louis.load_table(tables) {
+            Err(Error::InvalidTable(e)) => {
+                assert!(e.errors().any(|error| error.message.contains(message)));
+            }
+            other => panic!("expected InvalidTable, got {:?}", other),
+        }
+    }
+
+    louis.reset_table_resolver();
+    assert!(!contents_dir.exists());
+    assert!(louis.load_table("louis-rs-virtual-3.utb").is_err());
+    fs::remove_file(&part).unwrap();
+}
+
+#[test]
+fn table_info() {
+    use super::metadata::{Contraction, TableType};
+    let louis = API.lock().unwrap();
+    let info = louis.table_info("en-us-g1.ctb").unwrap();
+    assert_eq!(info.table, "en-us-g1.ctb");
+    assert_eq!(info.name.as_deref(), Some("U.S. English grade 1 (EBAE)"));
+    assert_eq!(info.locale.as_ref().map(|l| l.region()), Some(Some("US")));
+    assert_eq!(info.grade, Some(1.0));
+    assert_eq!(info.contraction, Some(Contraction::No));
+    assert_eq!(info.table_type, Some(TableType::Literary));
+    assert_eq!(info.system.as_deref(), Some("ebae"));
+    assert_eq!(info.dots, None);
+    assert_eq!(info.hyphenation, None);
+
+    match louis.table_info("no_such_table.utb") {
+        Err(Error::InvalidTable(e)) => {
+            let error = e.errors().next().unwrap();
+            assert_eq!(error.message, "Cannot resolve table 'no_such_table.utb'");
+        }
+        other => panic!("expected InvalidTable, got {:?}", other),
+    }
+}
+
+#[test]
+fn find_tables() {
+    use super::metadata::{Dots, TableQuery, TableType};
+    let mut louis = API.lock().unwrap();
+    let query = TableQuery::new()
+        .locale("en-US".parse().unwrap())
+        .table_type(TableType::Computer)
+        .dots(Dots::Eight);
+    let found = louis.find_tables(&query).unwrap();
+    assert!(!found.is_empty());
+    assert!(found
+        .iter()
+        .all(|info| info.locale.as_ref().map(|l| l.as_str()) == Some("en-US")));
+    assert_eq!(louis.find_table(&query).unwrap(), found.first().cloned());
+
+    let nothing = TableQuery::new().locale("x-none".parse().unwrap());
+    assert_eq!(louis.find_table(&nothing).unwrap(), None);
+    assert!(louis.find_tables(&nothing).unwrap().is_empty());
+    match louis.find_table(&TableQuery::new().system("two words")) {
+        Err(Error::InvalidInput(_)) => (),
+        other => panic!("expected InvalidInput, got {:?}", other),
+    }
+
+    louis
+        .index_tables(&["en-us-g1.ctb", "en-ueb-g1.ctb"])
+        .unwrap();
+    let uncontracted = TableQuery::new().grade(1.0).system("ueb");
+    let found = louis.find_tables(&uncontracted).unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].table, "en-ueb-g1.ctb");
+    louis.index_tables::<&str>(&[]).unwrap();
+    assert!(
+        louis
+            .find_tables(&TableQuery::new().grade(1.0))
+            .unwrap()
+            .len()
+            > 2
+    );
+}
+
+#[test]
+fn table_for() {
+    use super::metadata::{Contraction, Dots};
+    let louis = API.lock().unwrap();
+    let pick = |locale: &str, contraction, dots| {
+        louis
+            .table_for(&locale.parse().unwrap(), contraction, dots)
+            .unwrap()
+            .map(|choice| choice.info.table)
+    };
+    let ends_with = |table: Option<String>, file: &str| table.is_some_and(|t| t.ends_with(file));
+    assert!(ends_with(
+        pick("de-CH", Contraction::Full, None),
+        "de_CH.tbl"
+    ));
+    assert!(ends_with(
+        pick("de-ch", Contraction::Full, None),
+        "de_CH.tbl"
+    ));
+    assert!(ends_with(
+        pick("en-GB", Contraction::No, None),
+        "en-gb-g1.utb"
+    ));
+    assert!(ends_with(
+        pick("ur-PK", Contraction::No, None),
+        "ur-pk-g1.utb"
+    ));
+    let eight_dots = louis
+        .table_for(
+            &"en-GB".parse().unwrap(),
+            Contraction::No,
+            Some(Dots::Eight),
+        )
+        .unwrap()
+        .unwrap();
+    assert!(eight_dots.info.table.ends_with("en-gb-g1.utb"));
+    assert_eq!(
+        eight_dots.to_string().rsplit(": ").next().unwrap(),
+        "locale en-GB matches; contraction no matches; \
+         the number of dots isn't declared, 8 were asked for"
+    );
+    // The 8-dot table declares the wrong number of dots, so the one that doesn't declare any wins
+    assert!(ends_with(
+        pick("no", Contraction::No, Some(Dots::Six)),
+        "no-no-g0.utb"
+    ));
+    assert_eq!(pick("xx", Contraction::No, None), None);
+
+    let ranked = louis
+        .tables_for(&"de-CH".parse().unwrap(), Contraction::No, None)
+        .unwrap();
+    let files: Vec<_> = ranked
+        .iter()
+        .map(|choice| choice.info.table.rsplit('/').next().unwrap())
+        .collect();
+    assert_eq!(files, ["de-ch-g0.utb", "de-de-g0.utb"]);
+    assert_eq!(ranked[0].reasons[0], "locale de-CH matches");
+
+    let by_grade = louis.tables_for(&"de".parse().unwrap(), 1.0, None).unwrap();
+    assert!(ends_with(
+        by_grade.first().map(|c| c.info.table.clone()),
+        "de-de-g1.ctb"
+    ));
+    assert!(by_grade[1].reasons[0].contains("more specific locale de-CH"));
+}
+
+#[test]
+fn translate_batch() {
+    use super::modes::DOTS_UNICODE;
+    let louis = API.lock().unwrap();
+    let long = "A bird in the hand is worth two in the bush. ".repeat(200);
+    let inputs = [
+        "This is an example sentence with a rare word: syzygy.",
+        &long,
+        "",
+        "nul\0byte",
+        "the hand",
+    ];
+    for &mode in &[TranslationModes::empty(), DOTS_UNICODE] {
+        let batch = louis.translate_batch("en-us-g2.ctb", &inputs, mode);
+        assert_eq!(batch.len(), inputs.len());
+        for (input, translation) in inputs.iter().zip(batch) {
+            match louis.translate("en-us-g2.ctb", input, mode) {
+                Ok(expected) => assert_eq!(translation.unwrap(), expected),
+                Err(Error::InvalidInput(_)) => match translation {
+                    Err(Error::InvalidInput(_)) => (),
+                    other => panic!("unexpected result: {:?}", other),
+                },
+                Err(e) => panic!("unexpected error: {:?}", e),
+            }
+        }
+    }
+
+    let batch = louis.translate_batch("no_such_table.utb", &inputs, TranslationModes::empty());
+    assert_eq!(batch.len(), inputs.len());
+    assert!(batch
+        .iter()
+        .all(|result| matches!(result, Err(Error::InvalidTable(_)))));
+}
+
+#[test]
+fn service_is_send_sync() {
+    // The service owns its own `Louis`, so it can only be tried out in the doc tests
+    fn assert_send_sync<T: Send + Sync + Clone>() {}
+    assert_send_sync::<super::LouisService>();
+}
+
+#[test]
+fn wire_round_trip() {
+    use super::braille::BrailleCell;
+    use super::emphasis::{Emphasis, EmphasisSpan};
+    use super::modes::{HyphenationMode, DOTS_UNICODE};
+    use super::process::{decode, encode, Request, Response};
+    use super::{Translation, TranslationOptions};
+
+    let options = TranslationOptions {
+        emphasis: vec![
+            EmphasisSpan::new(0..3, Emphasis::Emph(4)),
+            EmphasisSpan::new(4..8, Emphasis::Class("script".into())),
+        ],
+        cursor: Some(2),
+        hyphens: Some(vec![1, 5]),
+    };
+    let requests = [
+        Request::TranslateSimple {
+            table_names: "en_US.tbl".to_owned(),
+            input: "the hand".to_owned(),
+            backwards: true,
+            mode: DOTS_UNICODE,
+        },
+        Request::Translate {
+            table_names: "en_US.tbl".to_owned(),
+            input: "the hand".to_owned(),
+            mode: TranslationModes::empty(),
+            options: options.clone(),
+        },
+        Request::BackTranslate {
+            table_names: "en_US.tbl".to_owned(),
+            input: "! h&".to_owned(),
+            mode: TranslationModes::empty(),
+            options: TranslationOptions::default(),
+        },
+        Request::Hyphenate {
+            table_names: "hyph_en_US.dic".to_owned(),
+            word: "hyphenation".to_owned(),
+            mode: HyphenationMode::Braille,
+        },
+        Request::TranslateBatch {
+            table_names: "en_US.tbl".to_owned(),
+            inputs: vec!["the".to_owned(), String::new()],
+            mode: TranslationModes::empty(),
+        },
+    ];
+    for (tag, request) in requests.iter().enumerate() {
+        let frame = encode(request);
+        assert_eq!(frame[0] as usize, tag);
+        assert_eq!(&decode::<Request>(&frame).unwrap(), request);
+    }
+
+    let translation = Translation {
+        output: "⠖⠀⠓⠯".to_owned(),
+        braille: Some(
+            [0x16, 0, 0x13, 0x2f]
+                .iter()
+                .map(|&bits| BrailleCell::from_bits_truncate(bits))
+                .collect(),
+        ),
+        output_pos: vec![0, 0, 0, 1, 2, 3, 3, 3],
+        input_pos: vec![0, 3, 4, 5],
+        cursor: Some(0),
+        hyphens: None,
+    };
+    let responses = [
+        Response::Text("! h&".to_owned()),
+        Response::Translation(translation.clone()),
+        Response::Hyphens(vec![2, 6]),
+        Response::Translations(vec![
+            Ok(translation),
+            Err(Error::InvalidInput("nul byte".to_owned())),
+        ]),
+    ];
+    for (tag, response) in responses.iter().enumerate() {
+        let frame = encode(response);
+        assert_eq!(frame[0] as usize, tag);
+        // Responses can hold errors, which can't be compared
+        let decoded = decode::<Response>(&frame).unwrap();
+        assert_eq!(format!("{:?}", decoded), format!("{:?}", response));
+    }
 }
 
 #[test]
 fn example_lou_translate_forward_fr() {
     Command::new("cargo")
-        .args(&["run", "--example", "lou_translate", "--"])
+        .args(["run", "--example", "lou_translate", "--"])
         .arg("fr-bfu-g2.ctb")
-        .with_stdin().buffer("Le braille est un système d'écriture tactile à points saillants.")
-        .assert().success()
+        .with_stdin()
+        .buffer("Le braille est un système d'écriture tactile à points saillants.")
+        .assert()
+        .success()
         .stdout("¨l ;l û u sy d'é:iture tactile à pts s/|ôs.\n");
 }
 
 #[test]
 fn example_lou_translate_backward_fr() {
     Command::new("cargo")
-        .args(&["run", "--example", "lou_translate", "--"])
+        .args(["run", "--example", "lou_translate", "--"])
         .arg("-b")
         .arg("fr-bfu-g2.ctb")
-        .with_stdin().buffer("¨l ;l û u sy d'é:iture tactile à pts s/|ôs.")
-        .assert().success()
+        .with_stdin()
+        .buffer("¨l ;l û u sy d'é:iture tactile à pts s/|ôs.")
+        .assert()
+        .success()
         .stdout("Le braille est un système d'écriture tactile à points saillants.\n");
 }
 
@@ -114,23 +1380,29 @@ fn example_lou_translate_all_tables() {
         Let's hope it works!! ﾟ･✿ヾ╲(｡◕‿◕｡)╱✿･ﾟ\n";
 
     let louis = API.lock().unwrap();
-    let tables = louis.list_tables();
-    for table in tables {
+    let tables = louis.list_tables().unwrap();
+    for table in tables.iter().map(|info| &info.path) {
         let ours = Command::new("cargo")
-            .args(&["run", "--example", "lou_translate", "--"])
-            .arg(&table)
-            .with_stdin().buffer(sentence)
-            .assert().success()
+            .args(["run", "--example", "lou_translate", "--"])
+            .arg(table)
+            .with_stdin()
+            .buffer(sentence)
+            .assert()
+            .success()
             .get_output()
-            .stdout.clone();
+            .stdout
+            .clone();
 
         let expected = Command::new("lou_translate")
-            .arg(&table)
-            .with_stdin().buffer(sentence)
-            .assert().success()
+            .arg(table)
+            .with_stdin()
+            .buffer(sentence)
+            .assert()
+            .success()
             .get_output()
-            .stdout.clone();
-            
+            .stdout
+            .clone();
+
         assert_eq!(ours, expected);
     }
 }
@@ -0,0 +1,117 @@
+//! Tests of the asynchronous requests to a `LouisService`. Each service needs a `Louis` of its own,
+//! so they can't run alongside the unit tests, which share a single one.
+
+#![cfg(feature = "tokio")]
+
+extern crate louis;
+extern crate tokio;
+
+use louis::modes::TranslationModes;
+use louis::{Error, Louis, LouisFuture, LouisService};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
+use tokio::runtime::Runtime;
+
+/// There can only be one `Louis` at a time, so the tests take turns
+static TURN: Mutex<()> = Mutex::new(());
+
+/// Starts a service, which has to be dropped before the returned guard
+fn service(queue_len: usize) -> (MutexGuard<'static, ()>, LouisService) {
+    let turn = TURN.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let service = LouisService::new(Louis::new().unwrap(), queue_len).unwrap();
+    (turn, service)
+}
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+}
+
+/// Keeps the service's thread busy until the returned sender is used
+fn occupy(service: &LouisService) -> (LouisFuture<()>, mpsc::Sender<()>) {
+    let (started, start) = mpsc::channel();
+    let (proceed, wait) = mpsc::channel();
+    let busy = service.run_async(move |_| {
+        started.send(()).unwrap();
+        wait.recv().unwrap()
+    });
+    start.recv().unwrap();
+    (busy, proceed)
+}
+
+#[test]
+fn cancel_queued_request() {
+    let (_turn, service) = service(4);
+    let runtime = runtime();
+    let (busy, proceed) = occupy(&service);
+
+    let ran = Arc::new(AtomicUsize::new(0));
+    let counter = ran.clone();
+    let cancelled = service.run_async(move |_| counter.fetch_add(1, Ordering::SeqCst));
+    let counter = ran.clone();
+    let kept = service.run_async(move |_| counter.fetch_add(10, Ordering::SeqCst));
+    drop(cancelled);
+
+    proceed.send(()).unwrap();
+    runtime.block_on(busy).unwrap();
+    assert_eq!(runtime.block_on(kept).unwrap(), 0);
+    assert_eq!(ran.load(Ordering::SeqCst), 10);
+}
+
+#[test]
+fn full_queue() {
+    let (_turn, service) = service(1);
+    let runtime = runtime();
+    let (busy, proceed) = occupy(&service);
+
+    let queued = service.run_async(|_| 1);
+    let waiting =
+        service.translate_simple_async("en_US.tbl", "the hand", false, TranslationModes::empty());
+    match service.try_run(|_| ()) {
+        Err(Error::Unavailable) => (),
+        other => panic!("expected Unavailable, got {:?}", other),
+    }
+
+    proceed.send(()).unwrap();
+    runtime.block_on(busy).unwrap();
+    assert_eq!(runtime.block_on(waiting).unwrap(), "! h&");
+    assert_eq!(runtime.block_on(queued).unwrap(), 1);
+}
+
+#[test]
+fn cancel_frees_room() {
+    let (_turn, service) = service(1);
+    let runtime = runtime();
+    let (busy, proceed) = occupy(&service);
+
+    let cancelled = service.run_async(|_| ());
+    drop(cancelled);
+    // Queued right away, without being polled
+    let ran = Arc::new(AtomicUsize::new(0));
+    let counter = ran.clone();
+    let accepted = service.run_async(move |_| counter.fetch_add(1, Ordering::SeqCst));
+    match service.try_run(|_| ()) {
+        Err(Error::Unavailable) => (),
+        other => panic!("expected Unavailable, got {:?}", other),
+    }
+
+    proceed.send(()).unwrap();
+    runtime.block_on(busy).unwrap();
+    // Handled after `accepted`
+    service.run(|_| ()).unwrap();
+    assert_eq!(ran.load(Ordering::SeqCst), 1);
+    assert_eq!(runtime.block_on(accepted).unwrap(), 0);
+}
+
+#[test]
+fn stopped_service() {
+    let (_turn, service) = service(4);
+    let runtime = runtime();
+    service.shutdown();
+    match runtime.block_on(service.translate_async("en_US.tbl", "hand", TranslationModes::empty()))
+    {
+        Err(Error::ServiceStopped) => (),
+        other => panic!("expected ServiceStopped, got {:?}", other),
+    }
+}
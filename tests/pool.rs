@@ -0,0 +1,226 @@
+//! Tests of `LouisPool`. This executable doubles as the pool's helper process, which is why it
+//! has a `main` of its own instead of the usual test harness.
+
+extern crate louis;
+
+use louis::emphasis::{Emphasis, EmphasisSpan};
+use louis::modes::{HyphenationMode, TranslationModes};
+use louis::process::{self, Request};
+use louis::{Error, Louis, LouisPool, LouisPoolBuilder, TranslationOptions};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// Makes a helper announce itself, but never answer, so that requests to it time out
+const STALL_ENV: &str = "LOUIS_TEST_STALL";
+
+fn main() {
+    if env::var_os(STALL_ENV).is_some() && env::var_os(process::WORKER_ENV).is_some() {
+        stall();
+    }
+    process::run_worker_if_requested();
+
+    let mut tests: Vec<(&str, fn())> = vec![
+        ("pool", pool),
+        ("pool_timeout", pool_timeout),
+        ("pool_respawn_failure", pool_respawn_failure),
+    ];
+    #[cfg(unix)]
+    tests.push(("pool_rejects_other_programs", pool_rejects_other_programs));
+    #[cfg(unix)]
+    tests.push(("pool_memory_limit", pool_memory_limit));
+
+    println!("\nrunning {} tests", tests.len());
+    for &(name, test) in &tests {
+        println!("test {} ...", name);
+        test();
+    }
+    println!("\ntest result: ok. {} passed", tests.len());
+}
+
+fn stall() -> ! {
+    let hello = concat!("louis helper ", env!("CARGO_PKG_VERSION")).as_bytes();
+    let mut stdout = io::stdout();
+    stdout
+        .write_all(&(hello.len() as u32).to_le_bytes())
+        .unwrap();
+    stdout.write_all(hello).unwrap();
+    stdout.flush().unwrap();
+    // Until the pool gives up on this helper
+    io::copy(&mut io::stdin(), &mut io::sink()).unwrap();
+    std::process::exit(0);
+}
+
+/// Builds a pool whose initial helpers stall. Helpers started later to replace them don't.
+fn stalling(builder: LouisPoolBuilder) -> LouisPool {
+    env::set_var(STALL_ENV, "1");
+    let pool = builder.build();
+    env::remove_var(STALL_ENV);
+    pool.unwrap()
+}
+
+fn request() -> Request {
+    Request::TranslateSimple {
+        table_names: "en_US.tbl".to_owned(),
+        input: "the hand".to_owned(),
+        backwards: false,
+        mode: TranslationModes::empty(),
+    }
+}
+
+fn pool() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<LouisPool>();
+
+    let pool = LouisPool::new(3).unwrap();
+    assert_eq!(pool.workers(), 3);
+
+    let sentences = [
+        "This is an example sentence with a rare word: syzygy.",
+        "the hand",
+        "",
+        "Braille in several processes at once",
+    ];
+    let results = pool.translate_all("en_US.tbl", &sentences, TranslationModes::empty());
+    let louis = Louis::new().unwrap();
+    assert_eq!(results.len(), sentences.len());
+    for (sentence, result) in sentences.iter().zip(results) {
+        let expected = louis
+            .translate_simple("en_US.tbl", sentence, false, TranslationModes::empty())
+            .unwrap();
+        assert_eq!(result.unwrap(), expected);
+    }
+
+    let options = TranslationOptions {
+        emphasis: vec![EmphasisSpan::new(0..3, Emphasis::Italic)],
+        cursor: Some(2),
+        ..Default::default()
+    };
+    assert_eq!(
+        pool.translate_with_options(
+            "en-us-g2.ctb",
+            "the hand",
+            TranslationModes::empty(),
+            &options
+        )
+        .unwrap(),
+        louis
+            .translate_with_options(
+                "en-us-g2.ctb",
+                "the hand",
+                TranslationModes::empty(),
+                &options
+            )
+            .unwrap()
+    );
+    assert_eq!(
+        pool.hyphenate("en_US.tbl", "\"Hyphenation,\"", HyphenationMode::Print)
+            .unwrap(),
+        louis
+            .hyphenate("en_US.tbl", "\"Hyphenation,\"", HyphenationMode::Print)
+            .unwrap()
+    );
+
+    let paragraphs: Vec<_> = (0..50).map(|i| format!("Paragraph number {}", i)).collect();
+    let batch = pool.translate_batch("en_US.tbl", &paragraphs, TranslationModes::empty());
+    assert_eq!(
+        batch.into_iter().map(Result::unwrap).collect::<Vec<_>>(),
+        louis
+            .translate_batch("en_US.tbl", &paragraphs, TranslationModes::empty())
+            .into_iter()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>()
+    );
+
+    // Errors come back with their details, and don't affect the helpers
+    match pool.translate_simple(
+        "no_such_table.utb",
+        "hand",
+        false,
+        TranslationModes::empty(),
+    ) {
+        Err(Error::InvalidTable(e)) => {
+            assert_eq!(e.table_names, "no_such_table.utb");
+            assert!(!e.diagnostics.is_empty());
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert_eq!(
+        pool.translate_simple("en_US.tbl", "the hand", false, TranslationModes::empty())
+            .unwrap(),
+        "! h&"
+    );
+}
+
+#[cfg(unix)]
+fn pool_rejects_other_programs() {
+    // Exits without announcing itself as a helper
+    match LouisPool::with_program(1, "true") {
+        Err(Error::WorkerCrashed(_)) => (),
+        other => panic!("unexpected result: {:?}", other.map(|pool| pool.workers())),
+    }
+}
+
+fn pool_timeout() {
+    let pool = stalling(LouisPool::builder().workers(1));
+    match pool.execute_with_timeout(request(), Duration::from_millis(100)) {
+        Err(Error::Timeout) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+    // The helper has been replaced
+    assert_eq!(
+        pool.translate_simple("en_US.tbl", "the hand", false, TranslationModes::empty())
+            .unwrap(),
+        "! h&"
+    );
+    assert_eq!(pool.live_workers(), 1);
+}
+
+fn pool_respawn_failure() {
+    let program = env::temp_dir().join(format!("louis-rs-worker-{}", std::process::id()));
+    fs::copy(env::current_exe().unwrap(), &program).unwrap();
+    let pool = stalling(LouisPool::builder().workers(1).program(&program));
+    assert_eq!(pool.live_workers(), 1);
+    fs::remove_file(&program).unwrap();
+
+    // The helper times out and can't be replaced, which is what the request fails with
+    match pool.execute_with_timeout(request(), Duration::from_millis(100)) {
+        Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::NotFound => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert_eq!(pool.workers(), 1);
+    assert_eq!(pool.live_workers(), 0);
+    match pool.execute(request()) {
+        Err(Error::Io(_)) => (),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[cfg(unix)]
+fn pool_memory_limit() {
+    let pool = LouisPool::builder()
+        .workers(1)
+        .memory_limit(64 << 20)
+        .build()
+        .unwrap();
+    // The limit leaves enough room for ordinary requests
+    assert_eq!(
+        pool.translate_simple("en_US.tbl", "the hand", false, TranslationModes::empty())
+            .unwrap(),
+        "! h&"
+    );
+
+    // Merely receiving this exceeds the limit, so the helper aborts
+    let input = "a".repeat(64 << 20);
+    match pool.translate_simple("en_US.tbl", &input, false, TranslationModes::empty()) {
+        Err(Error::WorkerCrashed(_)) => (),
+        other => panic!("unexpected result: {:?}", other.map(|output| output.len())),
+    }
+    // The helper has been replaced
+    assert_eq!(
+        pool.translate_simple("en_US.tbl", "the hand", false, TranslationModes::empty())
+            .unwrap(),
+        "! h&"
+    );
+}